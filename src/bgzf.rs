@@ -0,0 +1,159 @@
+use flate2::read::DeflateDecoder;
+use flate2::Crc;
+use std::io::{self, prelude::*};
+use std::thread;
+
+/// Blocks decompressed per worker thread in each batch
+const BLOCKS_PER_THREAD: usize = 16;
+
+/// Length of the fixed part of a gzip member header
+const GZIP_HEADER_LEN: usize = 12;
+
+/// Whether the bytes start a BGZF block: a gzip member whose extra field
+/// carries a `BC` subfield
+pub fn is_bgzf(header: &[u8]) -> bool {
+    if header.len() < GZIP_HEADER_LEN + 6 {
+        return false;
+    }
+    let flags = header[3];
+    let xlen = u16::from_le_bytes([header[10], header[11]]) as usize;
+    header[0] == 0x1f
+        && header[1] == 0x8b
+        && header[2] == 8
+        && flags & 4 != 0
+        && xlen >= 6
+        && header[12] == b'B'
+        && header[13] == b'C'
+        && header[14] == 2
+        && header[15] == 0
+}
+
+/// Reader decompressing a BGZF stream with several threads
+///
+/// BGZF files are series of independent gzip blocks of at most 64 KiB, so
+/// batches of blocks can be inflated in parallel and concatenated in order.
+pub struct ParallelBgzfReader<R> {
+    inner: R,
+    threads: usize,
+    buffer: Vec<u8>,
+    pos: usize,
+    eof: bool,
+}
+
+impl<R: Read> ParallelBgzfReader<R> {
+    pub fn new(inner: R, threads: usize) -> Self {
+        ParallelBgzfReader {
+            inner,
+            threads: threads.max(1),
+            buffer: Vec::new(),
+            pos: 0,
+            eof: false,
+        }
+    }
+
+    /// Read the next compressed block, or `None` at the end of the stream
+    fn read_block(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut header = [0u8; GZIP_HEADER_LEN];
+        let mut filled = 0;
+        while filled < header.len() {
+            match self.inner.read(&mut header[filled..])? {
+                0 if filled == 0 => return Ok(None),
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                n => filled += n,
+            }
+        }
+        let xlen = u16::from_le_bytes([header[10], header[11]]) as usize;
+        let mut extra = vec![0u8; xlen];
+        self.inner.read_exact(&mut extra)?;
+        let mut full = header.to_vec();
+        full.extend_from_slice(&extra);
+        if !is_bgzf(&full) {
+            return Err(invalid_data("not a BGZF block"));
+        }
+        let block_size = u16::from_le_bytes([extra[4], extra[5]]) as usize + 1;
+        let rest = block_size
+            .checked_sub(GZIP_HEADER_LEN + xlen)
+            .filter(|rest| *rest >= 8)
+            .ok_or_else(|| invalid_data("BGZF block size too small"))?;
+        let mut block = vec![0u8; rest];
+        self.inner.read_exact(&mut block)?;
+        Ok(Some(block))
+    }
+
+    /// Decompress the next batch of blocks into the buffer
+    fn fill(&mut self) -> io::Result<()> {
+        let mut blocks = Vec::with_capacity(self.threads * BLOCKS_PER_THREAD);
+        while blocks.len() < self.threads * BLOCKS_PER_THREAD {
+            match self.read_block()? {
+                Some(block) => blocks.push(block),
+                None => {
+                    self.eof = true;
+                    break;
+                }
+            }
+        }
+
+        let per_thread = blocks.len().div_ceil(self.threads).max(1);
+        let inflated = thread::scope(|scope| {
+            let handles = blocks
+                .chunks(per_thread)
+                .map(|batch| {
+                    scope.spawn(move || {
+                        batch
+                            .iter()
+                            .map(|block| inflate_block(block))
+                            .collect::<io::Result<Vec<Vec<u8>>>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<io::Result<Vec<_>>>()
+        })?;
+
+        self.buffer.clear();
+        self.pos = 0;
+        for data in inflated.into_iter().flatten() {
+            self.buffer.extend_from_slice(&data);
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for ParallelBgzfReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Empty blocks (such as the EOF marker) decompress to nothing, so
+        // keep filling until there is data or the stream ends
+        while self.pos == self.buffer.len() {
+            if self.eof {
+                return Ok(0);
+            }
+            self.fill()?;
+        }
+        let n = buf.len().min(self.buffer.len() - self.pos);
+        buf[..n].copy_from_slice(&self.buffer[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Inflate the body of a BGZF block (deflate data, CRC32 and ISIZE) and
+/// check it against its trailer
+fn inflate_block(block: &[u8]) -> io::Result<Vec<u8>> {
+    let (data, trailer) = block.split_at(block.len() - 8);
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]) as usize;
+    let mut out = Vec::with_capacity(size);
+    DeflateDecoder::new(data).read_to_end(&mut out)?;
+    let mut check = Crc::new();
+    check.update(&out);
+    if out.len() != size || check.sum() != crc {
+        return Err(invalid_data("BGZF block failed its CRC or size check"));
+    }
+    Ok(out)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
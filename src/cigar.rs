@@ -0,0 +1,195 @@
+/// An alignment operation, as read from a `cg:Z` CIGAR or `cs:Z` tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlignOp {
+    /// Bases aligned on both query and target (match or mismatch)
+    Match(usize),
+    /// Bases present in the query only
+    Insertion(usize),
+    /// Bases present in the target only (deletions and skipped regions)
+    Deletion(usize),
+}
+
+/// Parse a CIGAR string such as `10M1D4M`
+///
+/// Returns `None` if the string is malformed or uses unknown operations.
+pub fn parse_cigar(cigar: &str) -> Option<Vec<AlignOp>> {
+    let mut ops = Vec::new();
+    let mut len: usize = 0;
+    let mut has_len = false;
+    for c in cigar.chars() {
+        if let Some(d) = c.to_digit(10) {
+            len = len.checked_mul(10)?.checked_add(d as usize)?;
+            has_len = true;
+            continue;
+        }
+        if !has_len {
+            return None;
+        }
+        let op = match c {
+            'M' | '=' | 'X' => AlignOp::Match(len),
+            'I' => AlignOp::Insertion(len),
+            'D' | 'N' => AlignOp::Deletion(len),
+            // Clipping and padding consume no target bases
+            'S' | 'H' | 'P' => {
+                len = 0;
+                has_len = false;
+                continue;
+            }
+            _ => return None,
+        };
+        ops.push(op);
+        len = 0;
+        has_len = false;
+    }
+    if has_len {
+        return None;
+    }
+    Some(ops)
+}
+
+/// Parse a minimap2-style difference string such as `:10-a:4` or
+/// `=ACGT*ag+tt`
+///
+/// Returns `None` if the string is malformed.
+pub fn parse_cs(cs: &str) -> Option<Vec<AlignOp>> {
+    let bytes = cs.as_bytes();
+    let mut ops = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let op = bytes[i];
+        i += 1;
+        let start = i;
+        match op {
+            b':' => {
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    i += 1;
+                }
+                ops.push(AlignOp::Match(cs[start..i].parse().ok()?));
+            }
+            b'*' => {
+                if i + 2 > bytes.len() {
+                    return None;
+                }
+                i += 2;
+                ops.push(AlignOp::Match(1));
+            }
+            b'=' | b'+' | b'-' => {
+                while i < bytes.len() && bytes[i].is_ascii_alphabetic() {
+                    i += 1;
+                }
+                let len = i - start;
+                if len == 0 {
+                    return None;
+                }
+                ops.push(match op {
+                    b'=' => AlignOp::Match(len),
+                    b'+' => AlignOp::Insertion(len),
+                    _ => AlignOp::Deletion(len),
+                });
+            }
+            b'~' => {
+                // Intron: two splice-site bases, a length, two splice-site bases
+                i += 2;
+                let digits = i;
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let len = cs.get(digits..i)?.parse().ok()?;
+                i += 2;
+                if i > bytes.len() {
+                    return None;
+                }
+                ops.push(AlignOp::Deletion(len));
+            }
+            _ => return None,
+        }
+    }
+    Some(ops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use AlignOp::*;
+
+    #[test]
+    fn cigar_operations() {
+        assert_eq!(
+            parse_cigar("10M1D4M2I3=1X"),
+            Some(vec![
+                Match(10),
+                Deletion(1),
+                Match(4),
+                Insertion(2),
+                Match(3),
+                Match(1)
+            ])
+        );
+        assert_eq!(parse_cigar(""), Some(vec![]));
+    }
+
+    #[test]
+    fn cigar_skips_clipping_and_padding() {
+        assert_eq!(
+            parse_cigar("5S3H10M2P4M6S"),
+            Some(vec![Match(10), Match(4)])
+        );
+    }
+
+    #[test]
+    fn cigar_skipped_region_is_deletion() {
+        assert_eq!(
+            parse_cigar("3M100N3M"),
+            Some(vec![Match(3), Deletion(100), Match(3)])
+        );
+    }
+
+    #[test]
+    fn malformed_cigar() {
+        assert_eq!(parse_cigar("M"), None);
+        assert_eq!(parse_cigar("10"), None);
+        assert_eq!(parse_cigar("10M5"), None);
+        assert_eq!(parse_cigar("10Q"), None);
+        assert_eq!(parse_cigar("4M-2D"), None);
+        assert_eq!(parse_cigar("99999999999999999999999M"), None);
+    }
+
+    #[test]
+    fn cs_operations() {
+        assert_eq!(
+            parse_cs(":6+ac:1-ggg:1*ag:7"),
+            Some(vec![
+                Match(6),
+                Insertion(2),
+                Match(1),
+                Deletion(3),
+                Match(1),
+                Match(1),
+                Match(7)
+            ])
+        );
+        assert_eq!(
+            parse_cs("=ACGT*ag=TT"),
+            Some(vec![Match(4), Match(1), Match(2)])
+        );
+    }
+
+    #[test]
+    fn cs_intron_is_deletion() {
+        assert_eq!(
+            parse_cs(":5~gt120ag:5"),
+            Some(vec![Match(5), Deletion(120), Match(5)])
+        );
+    }
+
+    #[test]
+    fn malformed_cs() {
+        assert_eq!(parse_cs(":"), None);
+        assert_eq!(parse_cs(":5*a"), None);
+        assert_eq!(parse_cs(":5+"), None);
+        assert_eq!(parse_cs("-:5"), None);
+        assert_eq!(parse_cs(":5~gtag"), None);
+        assert_eq!(parse_cs(":5~gt12a"), None);
+        assert_eq!(parse_cs("5M"), None);
+    }
+}
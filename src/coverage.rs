@@ -0,0 +1,379 @@
+use crate::depth::BaseDepth;
+use crate::error::RecordError;
+use crate::gaf::GafRecord;
+use crate::graph::GraphLengths;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// What an alignment adds to the coverage of each node it covers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CountMode {
+    /// Number of aligned bases on the node
+    #[default]
+    Bases,
+    /// 1 for every alignment covering at least one base of the node
+    Reads,
+    /// Fraction of the node's bases covered by the alignment
+    Fraction,
+}
+
+/// Smallest part of a node an alignment step must span for the node to be
+/// credited
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MinOverlap {
+    /// Number of target bases
+    Bases(usize),
+    /// Fraction of the node's length
+    Fraction(f64),
+}
+
+impl Default for MinOverlap {
+    fn default() -> Self {
+        MinOverlap::Bases(0)
+    }
+}
+
+impl MinOverlap {
+    /// Whether `span` target bases of a node of length `len` are enough
+    pub fn allows(&self, span: usize, len: usize) -> bool {
+        match *self {
+            MinOverlap::Bases(bases) => span >= bases,
+            MinOverlap::Fraction(fraction) => span as f64 >= fraction * len as f64,
+        }
+    }
+}
+
+impl FromStr for MinOverlap {
+    type Err = String;
+
+    /// Parse an integer as bases, or a decimal number in `[0, 1]` as a
+    /// fraction of node length
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(bases) = s.parse::<usize>() {
+            return Ok(MinOverlap::Bases(bases));
+        }
+        match s.parse::<f64>() {
+            Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(MinOverlap::Fraction(fraction)),
+            _ => Err(format!(
+                "`{}` is neither a number of bases nor a fraction between 0 and 1",
+                s
+            )),
+        }
+    }
+}
+
+/// Settings controlling how records are turned into coverage
+#[derive(Debug, Clone, Default)]
+pub struct CoverageOptions {
+    /// Credit only matched/mismatched target bases using the `cg:Z` or
+    /// `cs:Z` tag, when present
+    pub use_cigar: bool,
+    /// Accumulate bases deleted from the target in a separate vector
+    /// (requires `use_cigar`)
+    pub count_deletions: bool,
+    /// What each alignment adds to node coverage (and strand coverage);
+    /// deletions are always counted in bases
+    pub count_mode: CountMode,
+    /// Skip steps spanning less of their node than this, in node coverage
+    /// and per-base depth
+    pub min_node_overlap: MinOverlap,
+    /// Also track the depth of every base within each node
+    pub base_depth: bool,
+    /// Also accumulate coverage separately for alignments running along
+    /// each node forward and in reverse
+    pub stranded: bool,
+    /// Also count traversals of the graph's links (which must be loaded
+    /// with [`GraphLengths::load_links`])
+    pub edges: bool,
+}
+
+/// Per-node coverage accumulated from GAF records
+#[derive(Debug)]
+pub struct Coverage {
+    options: CoverageOptions,
+    values: Vec<f64>,
+    deletions: Option<Vec<f64>>,
+    /// Forward and reverse coverage, if tracked
+    strands: Option<(Vec<f64>, Vec<f64>)>,
+    /// Per-base depth, shared with the accumulators made by
+    /// [`Coverage::fork`] so worker threads do not each hold a copy
+    base_depth: Option<Arc<BaseDepth>>,
+    edges: Option<Vec<f64>>,
+    unlinked: usize,
+}
+
+impl Coverage {
+    /// Create an empty coverage vector sized for the given graph
+    pub fn new(graph: &GraphLengths) -> Self {
+        Self::with_options(graph, CoverageOptions::default())
+    }
+
+    /// Create an empty coverage vector with the given accumulation settings
+    pub fn with_options(graph: &GraphLengths, options: CoverageOptions) -> Self {
+        let deletions =
+            (options.use_cigar && options.count_deletions).then(|| vec![0.0; graph.len()]);
+        let strands = options
+            .stranded
+            .then(|| (vec![0.0; graph.len()], vec![0.0; graph.len()]));
+        let base_depth = options.base_depth.then(|| Arc::new(BaseDepth::new(graph)));
+        let edges = options.edges.then(|| vec![0.0; graph.links().len()]);
+        Coverage {
+            options,
+            values: vec![0.0; graph.len()],
+            deletions,
+            strands,
+            base_depth,
+            edges,
+            unlinked: 0,
+        }
+    }
+
+    /// Create an empty accumulator with the same options that adds per-base
+    /// depth to this one's array instead of its own
+    ///
+    /// Merging a fork back skips the shared depth, which it already holds.
+    pub fn fork(&self, graph: &GraphLengths) -> Self {
+        let mut fork = Self::with_options(
+            graph,
+            CoverageOptions {
+                base_depth: false,
+                ..self.options.clone()
+            },
+        );
+        fork.options.base_depth = self.options.base_depth;
+        fork.base_depth = self.base_depth.clone();
+        fork
+    }
+
+    /// Add what an alignment covers, as set by the count mode, scaled by
+    /// `weight`
+    ///
+    /// A record that does not fit the graph is rejected without changing
+    /// the coverage.
+    pub fn add_record(
+        &mut self,
+        graph: &GraphLengths,
+        record: &GafRecord,
+        weight: f64,
+    ) -> Result<(), RecordError> {
+        let ops = if self.options.use_cigar {
+            record.alignment_ops()?
+        } else {
+            None
+        };
+        // (node_index, covered, deleted) bases of each step, in path order
+        let mut steps = Vec::new();
+        if let Some(ops) = &ops {
+            record.for_each_step_with_ops(graph, ops, |index, matched, deleted| {
+                steps.push((index, matched, deleted));
+            })?;
+        } else {
+            record.for_each_step(graph, |index, len| steps.push((index, len, 0)))?;
+        }
+
+        let step_strands = self.strands.as_ref().map(|_| record.step_strands());
+        // Share of each node already credited to this record, for the
+        // reads and fraction modes
+        let mut credited: HashMap<usize, f64> = HashMap::new();
+        // Steps spanning too little of their node, which per-base depth
+        // skips too
+        let mut too_short = vec![false; steps.len()];
+        for (step, (index, covered, deleted)) in steps.into_iter().enumerate() {
+            if !self
+                .options
+                .min_node_overlap
+                .allows(covered + deleted, graph.node_len(index))
+            {
+                too_short[step] = true;
+                continue;
+            }
+            let amount = match self.options.count_mode {
+                CountMode::Bases => covered as f64,
+                _ if covered == 0 => 0.0,
+                mode => {
+                    // Credit each node at most once per record
+                    let share = credited.entry(index).or_insert(0.0);
+                    let fraction = match mode {
+                        CountMode::Reads => 1.0,
+                        _ => covered as f64 / graph.node_len(index) as f64,
+                    };
+                    let amount = fraction.min(1.0 - *share);
+                    *share += amount;
+                    amount
+                }
+            } * weight;
+            self.values[index] += amount;
+            if let (Some((forward, reverse)), Some(step_strands)) =
+                (&mut self.strands, &step_strands)
+            {
+                if step_strands[step] {
+                    reverse[index] += amount;
+                } else {
+                    forward[index] += amount;
+                }
+            }
+            if let Some(deletions) = &mut self.deletions {
+                deletions[index] += deleted as f64 * weight;
+            }
+        }
+
+        // The walk above has validated the record against the graph
+        if let Some(depth) = &self.base_depth {
+            record.for_each_covered_interval(graph, ops.as_deref(), |step, index, from, to| {
+                if !too_short[step] {
+                    depth.add(index, from, to, weight);
+                }
+            })?;
+        }
+        if let Some(edges) = &mut self.edges {
+            let unlinked = &mut self.unlinked;
+            record.for_each_edge(graph, |from, to| {
+                match graph.link_between(from.index, from.reverse, to.index, to.reverse) {
+                    Some(link) => edges[link] += weight,
+                    None => *unlinked += 1,
+                }
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Add the coverage accumulated by another accumulator over the same graph
+    pub fn merge(&mut self, other: &Coverage) {
+        for (v, o) in self.values.iter_mut().zip(&other.values) {
+            *v += o;
+        }
+        if let (Some(deletions), Some(other)) = (&mut self.deletions, &other.deletions) {
+            for (d, o) in deletions.iter_mut().zip(other) {
+                *d += o;
+            }
+        }
+        if let (Some((forward, reverse)), Some((other_forward, other_reverse))) =
+            (&mut self.strands, &other.strands)
+        {
+            for (v, o) in forward.iter_mut().zip(other_forward) {
+                *v += o;
+            }
+            for (v, o) in reverse.iter_mut().zip(other_reverse) {
+                *v += o;
+            }
+        }
+        if let (Some(depth), Some(other)) = (&self.base_depth, &other.base_depth) {
+            if !Arc::ptr_eq(depth, other) {
+                depth.merge(other);
+            }
+        }
+        if let (Some(edges), Some(other)) = (&mut self.edges, &other.edges) {
+            for (e, o) in edges.iter_mut().zip(other) {
+                *e += o;
+            }
+        }
+        self.unlinked += other.unlinked;
+    }
+
+    /// Raw coverage values in dense index order
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// Raw deleted-base counts in dense index order, if tracked
+    pub fn deletions(&self) -> Option<&[f64]> {
+        self.deletions.as_deref()
+    }
+
+    /// Coverage from alignments running along each node forward and in
+    /// reverse, in dense index order, if tracked
+    pub fn strands(&self) -> Option<(&[f64], &[f64])> {
+        self.strands
+            .as_ref()
+            .map(|(forward, reverse)| (forward.as_slice(), reverse.as_slice()))
+    }
+
+    /// Per-base depth within nodes, if tracked
+    pub fn base_depth(&self) -> Option<&BaseDepth> {
+        self.base_depth.as_deref()
+    }
+
+    /// Link traversal counts in L-line order, if tracked
+    pub fn edges(&self) -> Option<&[f64]> {
+        self.edges.as_deref()
+    }
+
+    /// Number of traversed step junctions that match no loaded link
+    pub fn unlinked(&self) -> usize {
+        self.unlinked
+    }
+
+    /// Coverage values, optionally divided by node length
+    ///
+    /// Zero-length nodes (including gap placeholders) scale to 0.
+    pub fn scaled(&self, graph: &GraphLengths, len_scale: bool) -> Vec<f64> {
+        scale_by_length(&self.values, graph, len_scale)
+    }
+
+    /// Forward and reverse coverage, optionally divided by node length
+    pub fn scaled_strands(
+        &self,
+        graph: &GraphLengths,
+        len_scale: bool,
+    ) -> Option<(Vec<f64>, Vec<f64>)> {
+        self.strands.as_ref().map(|(forward, reverse)| {
+            (
+                scale_by_length(forward, graph, len_scale),
+                scale_by_length(reverse, graph, len_scale),
+            )
+        })
+    }
+
+    /// Deleted-base counts, optionally divided by node length
+    pub fn scaled_deletions(&self, graph: &GraphLengths, len_scale: bool) -> Option<Vec<f64>> {
+        self.deletions
+            .as_ref()
+            .map(|deletions| scale_by_length(deletions, graph, len_scale))
+    }
+}
+
+fn scale_by_length(values: &[f64], graph: &GraphLengths, len_scale: bool) -> Vec<f64> {
+    values
+        .iter()
+        .zip(graph.segment_lengths())
+        .map(|(v, len)| match (len_scale, *len) {
+            (false, _) => *v,
+            (true, 0) => 0.0,
+            (true, len) => v / len as f64,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn min_node_overlap_applies_to_base_depth() {
+        let segments = [("1", 4), ("2", 3), ("3", 5)]
+            .iter()
+            .map(|(name, len)| (name.to_string(), *len))
+            .collect();
+        let graph = GraphLengths::from_segments(segments, Default::default());
+        let options = CoverageOptions {
+            min_node_overlap: MinOverlap::Bases(3),
+            base_depth: true,
+            ..Default::default()
+        };
+        let mut coverage = Coverage::with_options(&graph, options);
+        for line in [
+            // Spans only the last 2 bases of node 1, which is not credited
+            "r1\t10\t0\t10\t+\t>1>2>3\t12\t2\t12\t10\t10\t60",
+            "r2\t7\t0\t7\t+\t>1>2\t7\t0\t7\t7\t7\t60",
+        ] {
+            let record = GafRecord::parse(line).unwrap();
+            coverage.add_record(&graph, &record, 1.0).unwrap();
+        }
+        assert_eq!(coverage.values(), [4.0, 6.0, 5.0]);
+        let depth = coverage.base_depth().unwrap();
+        assert_eq!(depth.runs(0), [(0, 4, 1.0)]);
+        assert_eq!(depth.runs(1), [(0, 3, 2.0)]);
+        assert_eq!(depth.runs(2), [(0, 5, 1.0)]);
+    }
+}
@@ -0,0 +1,78 @@
+use crate::graph::GraphLengths;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Depths closer than this are treated as equal when building runs, so
+/// rounding left by weighted additions does not split them
+const DEPTH_EPSILON: f64 = 1e-9;
+
+/// Per-base depth along every node, accumulated as difference arrays
+///
+/// Each node gets `len + 1` slots in one flat vector, so memory is about
+/// 8 bytes per graph base. Slots are updated atomically, so one instance
+/// can be shared by every worker thread.
+#[derive(Debug)]
+pub struct BaseDepth {
+    offsets: Vec<usize>,
+    /// `f64` differences stored as their bits
+    diff: Vec<AtomicU64>,
+}
+
+impl BaseDepth {
+    /// Create zero depth over every node of the graph
+    pub fn new(graph: &GraphLengths) -> Self {
+        let mut offsets = Vec::with_capacity(graph.len() + 1);
+        let mut total = 0;
+        for len in graph.segment_lengths() {
+            offsets.push(total);
+            total += len + 1;
+        }
+        offsets.push(total);
+        BaseDepth {
+            offsets,
+            diff: (0..total)
+                .map(|_| AtomicU64::new(0.0f64.to_bits()))
+                .collect(),
+        }
+    }
+
+    /// Add `weight` to the depth of bases `[from, to)` of a node
+    pub fn add(&self, index: usize, from: usize, to: usize, weight: f64) {
+        let offset = self.offsets[index];
+        add_to(&self.diff[offset + from], weight);
+        add_to(&self.diff[offset + to], -weight);
+    }
+
+    /// Add the depth accumulated by another instance over the same graph
+    pub fn merge(&self, other: &BaseDepth) {
+        for (d, o) in self.diff.iter().zip(&other.diff) {
+            add_to(d, f64::from_bits(o.load(Ordering::Relaxed)));
+        }
+    }
+
+    /// Run-length encoded depth of a node as `(start, end, depth)` runs of
+    /// constant non-zero depth, in the node's forward coordinates
+    pub fn runs(&self, index: usize) -> Vec<(usize, usize, f64)> {
+        let offset = self.offsets[index];
+        let len = self.offsets[index + 1] - offset - 1;
+        let mut runs: Vec<(usize, usize, f64)> = Vec::new();
+        let mut depth = 0.0;
+        for pos in 0..len {
+            depth += f64::from_bits(self.diff[offset + pos].load(Ordering::Relaxed));
+            if depth.abs() < DEPTH_EPSILON {
+                continue;
+            }
+            match runs.last_mut() {
+                Some(run) if run.1 == pos && (run.2 - depth).abs() < DEPTH_EPSILON => run.1 += 1,
+                _ => runs.push((pos, pos + 1, depth)),
+            }
+        }
+        runs
+    }
+}
+
+/// Atomically add `value` to an `f64` stored as bits
+fn add_to(slot: &AtomicU64, value: f64) {
+    let _ = slot.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+        Some((f64::from_bits(bits) + value).to_bits())
+    });
+}
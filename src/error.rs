@@ -0,0 +1,104 @@
+use std::fmt;
+use std::io;
+
+/// Problem with a single input record, without file context
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordError {
+    /// Name of the offending field, e.g. `path_start`
+    pub field: &'static str,
+    /// Offending value as found in the input
+    pub value: String,
+    pub message: String,
+}
+
+impl RecordError {
+    pub fn new(field: &'static str, value: impl Into<String>, message: impl Into<String>) -> Self {
+        RecordError {
+            field,
+            value: value.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} `{}`: {}", self.field, self.value, self.message)
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+/// Error raised while reading gafpack inputs or writing its output
+#[derive(Debug)]
+pub enum Error {
+    /// Failure opening, reading or writing a file
+    Io { path: String, source: io::Error },
+    /// Malformed or inconsistent record at a 1-based line of a file
+    Record {
+        path: String,
+        line: usize,
+        source: RecordError,
+    },
+    /// Query whose records recur after another query's, at a 1-based line
+    /// of input that was expected to be grouped by query
+    Ungrouped {
+        path: String,
+        line: usize,
+        query: String,
+    },
+}
+
+impl Error {
+    pub fn io(path: impl Into<String>, source: io::Error) -> Self {
+        Error::Io {
+            path: path.into(),
+            source,
+        }
+    }
+
+    pub fn record(path: impl Into<String>, line: usize, source: RecordError) -> Self {
+        Error::Record {
+            path: path.into(),
+            line,
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path, source),
+            Error::Record { path, line, source } => write!(f, "{}:{}: {}", path, line, source),
+            Error::Ungrouped { path, line, query } => write!(
+                f,
+                "{}:{}: query `{}` recurs after its records ended; the input is not grouped by \
+                 query",
+                path, line, query
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Record { source, .. } => Some(source),
+            Error::Ungrouped { .. } => None,
+        }
+    }
+}
+
+/// What to do with a record that cannot be parsed or does not fit the graph
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ErrorPolicy {
+    /// Stop with an error
+    #[default]
+    Fail,
+    /// Drop the record silently
+    Skip,
+    /// Drop the record and print a warning on stderr
+    Warn,
+}
@@ -0,0 +1,112 @@
+use crate::gaf::GafRecord;
+
+/// Why a record was excluded from coverage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterReason {
+    /// Mapping quality below `min_mapq`
+    Mapq,
+    /// Identity below `min_identity`
+    Identity,
+    /// Alignment block length below `min_aligned_length`
+    AlignedLength,
+    /// Secondary alignment while keeping primaries only
+    Secondary,
+}
+
+/// Thresholds a GAF record must pass to contribute coverage, and the
+/// weight it then contributes with
+#[derive(Debug, Clone)]
+pub struct RecordFilter {
+    /// Minimum mapping quality (GAF column 12)
+    pub min_mapq: u32,
+    /// Minimum alignment identity, in [0, 1]
+    pub min_identity: f64,
+    /// Minimum alignment block length (GAF column 11)
+    pub min_aligned_length: usize,
+    /// Drop secondary alignments (`tp:A:S`)
+    pub primary_only: bool,
+    /// Coverage weight of secondary alignments relative to primaries
+    pub secondary_weight: f64,
+}
+
+impl Default for RecordFilter {
+    fn default() -> Self {
+        RecordFilter {
+            min_mapq: 0,
+            min_identity: 0.0,
+            min_aligned_length: 0,
+            primary_only: false,
+            secondary_weight: 1.0,
+        }
+    }
+}
+
+impl RecordFilter {
+    /// Return the reason the record is rejected, or `None` if it is kept
+    ///
+    /// Unmapped records are always kept, as they contribute no coverage.
+    pub fn rejects(&self, record: &GafRecord) -> Option<FilterReason> {
+        if record.is_unmapped() {
+            return None;
+        }
+        if self.primary_only && record.is_secondary() {
+            return Some(FilterReason::Secondary);
+        }
+        if record.mapq < self.min_mapq {
+            return Some(FilterReason::Mapq);
+        }
+        if record.block_len < self.min_aligned_length {
+            return Some(FilterReason::AlignedLength);
+        }
+        if self.min_identity > 0.0 && record.identity() < self.min_identity {
+            return Some(FilterReason::Identity);
+        }
+        None
+    }
+
+    /// Coverage weight of a kept record
+    pub fn weight(&self, record: &GafRecord) -> f64 {
+        if record.is_secondary() {
+            self.secondary_weight
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Counts of records dropped by a [`RecordFilter`], per reason
+#[derive(Debug, Clone, Default)]
+pub struct FilterStats {
+    pub mapq: usize,
+    pub identity: usize,
+    pub aligned_length: usize,
+    pub secondary: usize,
+    /// Records skipped under the `skip`/`warn` error policies
+    pub invalid: usize,
+}
+
+impl FilterStats {
+    /// Count a rejected record
+    pub fn add(&mut self, reason: FilterReason) {
+        match reason {
+            FilterReason::Mapq => self.mapq += 1,
+            FilterReason::Identity => self.identity += 1,
+            FilterReason::AlignedLength => self.aligned_length += 1,
+            FilterReason::Secondary => self.secondary += 1,
+        }
+    }
+
+    /// Add the counts of another pass
+    pub fn merge(&mut self, other: &FilterStats) {
+        self.mapq += other.mapq;
+        self.identity += other.identity;
+        self.aligned_length += other.aligned_length;
+        self.secondary += other.secondary;
+        self.invalid += other.invalid;
+    }
+
+    /// Total number of rejected records, including invalid ones
+    pub fn total(&self) -> usize {
+        self.mapq + self.identity + self.aligned_length + self.secondary + self.invalid
+    }
+}
@@ -0,0 +1,646 @@
+use crate::cigar::{parse_cigar, parse_cs, AlignOp};
+use crate::error::RecordError;
+use crate::graph::GraphLengths;
+
+/// A step of an alignment path, laid out along the path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathStep {
+    /// Dense node index
+    pub index: usize,
+    /// Whether the node is traversed in reverse (`<`)
+    pub reverse: bool,
+    /// Offset of the node's first traversed base along the path
+    pub start: usize,
+    /// Node length
+    pub len: usize,
+}
+
+/// A GAF alignment record, borrowing its fields from the input line
+///
+/// Only the twelve mandatory columns are parsed; optional SAM-style tags
+/// are kept verbatim in `tags`. Numeric columns given as `*` (as in
+/// unmapped records) are read as 0.
+#[derive(Debug, Clone)]
+pub struct GafRecord<'a> {
+    pub query_name: &'a str,
+    pub query_len: usize,
+    pub query_start: usize,
+    pub query_end: usize,
+    pub strand: char,
+    pub path: &'a str,
+    pub path_len: usize,
+    pub path_start: usize,
+    pub path_end: usize,
+    pub matches: usize,
+    pub block_len: usize,
+    pub mapq: u32,
+    pub tags: Vec<&'a str>,
+}
+
+impl<'a> GafRecord<'a> {
+    /// Parse a tab-separated GAF line
+    pub fn parse(line: &'a str) -> Result<Self, RecordError> {
+        let mut fields = line.split('\t');
+        let mut next = |field: &'static str| {
+            fields
+                .next()
+                .ok_or_else(|| RecordError::new(field, "", "missing column"))
+        };
+        let query_name = next("query_name")?;
+        let query_len = parse_num("query_len", next("query_len")?)?;
+        let query_start = parse_num("query_start", next("query_start")?)?;
+        let query_end = parse_num("query_end", next("query_end")?)?;
+        let strand = next("strand")?.chars().next().unwrap_or('*');
+        let path = next("path")?;
+        let path_len = parse_num("path_len", next("path_len")?)?;
+        let path_start = parse_num("path_start", next("path_start")?)?;
+        let path_end = parse_num("path_end", next("path_end")?)?;
+        let matches = parse_num("matches", next("matches")?)?;
+        let block_len = parse_num("block_len", next("block_len")?)?;
+        let mapq = parse_num("mapq", next("mapq")?)?;
+        let mapq = u32::try_from(mapq)
+            .map_err(|_| RecordError::new("mapq", mapq.to_string(), "out of range"))?;
+        Ok(GafRecord {
+            query_name,
+            query_len,
+            query_start,
+            query_end,
+            strand,
+            path,
+            path_len,
+            path_start,
+            path_end,
+            matches,
+            block_len,
+            mapq,
+            tags: fields.collect(),
+        })
+    }
+
+    /// Whether the record is unaligned (path is `*`)
+    pub fn is_unmapped(&self) -> bool {
+        self.path == "*"
+    }
+
+    /// Value of an optional `key:type:value` tag, if present
+    pub fn tag(&self, key: &str) -> Option<&'a str> {
+        self.tags.iter().find_map(|tag| {
+            let rest = tag.strip_prefix(key)?.strip_prefix(':')?;
+            let (_type, value) = rest.split_once(':')?;
+            Some(value)
+        })
+    }
+
+    /// Alignment identity, from the `id:f` tag if present and otherwise
+    /// residue matches over alignment block length (columns 10 and 11)
+    pub fn identity(&self) -> f64 {
+        if let Some(id) = self.tag("id").and_then(|v| v.parse::<f64>().ok()) {
+            return id;
+        }
+        if self.block_len == 0 {
+            0.0
+        } else {
+            self.matches as f64 / self.block_len as f64
+        }
+    }
+
+    /// Whether the `tp:A` tag marks this as a secondary alignment (`S` or `i`)
+    ///
+    /// Records without a `tp` tag are treated as primary.
+    pub fn is_secondary(&self) -> bool {
+        matches!(self.tag("tp"), Some("S") | Some("i"))
+    }
+
+    /// Alignment operations from the `cg:Z` tag, or the `cs:Z` tag if there
+    /// is no CIGAR; `None` if neither is present, and an error if the tag
+    /// used is malformed
+    pub fn alignment_ops(&self) -> Result<Option<Vec<AlignOp>>, RecordError> {
+        let (field, value, ops) = if let Some(cigar) = self.tag("cg") {
+            ("cg", cigar, parse_cigar(cigar))
+        } else if let Some(cs) = self.tag("cs") {
+            ("cs", cs, parse_cs(cs))
+        } else {
+            return Ok(None);
+        };
+        ops.map(Some)
+            .ok_or_else(|| RecordError::new(field, value, "malformed alignment operations"))
+    }
+
+    /// Key identifying the query group: name, start and end on the query,
+    /// the same columns [`query_columns`] reads from a raw line
+    pub fn query_key(&self) -> String {
+        format!(
+            "{}:{}:{}",
+            self.query_name, self.query_start, self.query_end
+        )
+    }
+
+    /// Segment names along the path, in walk order
+    pub fn steps(&self) -> impl Iterator<Item = &'a str> {
+        self.path.split(['<', '>']).filter(|s| !s.is_empty())
+    }
+
+    /// Segment names along the path with their orientation, `true` for a
+    /// reverse (`<`) traversal
+    pub fn oriented_steps(&self) -> Vec<(&'a str, bool)> {
+        split_walk(self.path)
+    }
+
+    /// Whether the query runs along each step's node in reverse: the step
+    /// orientation, flipped for alignments on the `-` strand
+    pub fn step_strands(&self) -> Vec<bool> {
+        let flip = self.strand == '-';
+        self.oriented_steps()
+            .into_iter()
+            .map(|(_, reverse)| reverse != flip)
+            .collect()
+    }
+
+    /// Resolve the path steps and lay them out along the path
+    pub fn layout(&self, graph: &GraphLengths) -> Result<Vec<PathStep>, RecordError> {
+        let mut start = 0;
+        self.oriented_steps()
+            .into_iter()
+            .map(|(name, reverse)| {
+                let index = graph
+                    .index_of(name)
+                    .ok_or_else(|| RecordError::new("path", name, "node not in graph"))?;
+                let len = graph.node_len(index);
+                let step = PathStep {
+                    index,
+                    reverse,
+                    start,
+                    len,
+                };
+                start += len;
+                Ok(step)
+            })
+            .collect()
+    }
+
+    /// Spans of the path, as `[start, end)` path coordinates, whose bases
+    /// are aligned: the whole target range, or only the matched and
+    /// mismatched stretches of `ops`
+    pub fn aligned_spans(&self, ops: Option<&[AlignOp]>) -> Vec<(usize, usize)> {
+        let Some(ops) = ops else {
+            return vec![(self.path_start, self.path_end)];
+        };
+        let mut spans = Vec::new();
+        let mut pos = self.path_start;
+        for op in ops {
+            match *op {
+                AlignOp::Match(len) => {
+                    spans.push((pos, pos + len));
+                    pos += len;
+                }
+                AlignOp::Deletion(len) => pos += len,
+                AlignOp::Insertion(_) => {}
+            }
+        }
+        spans
+    }
+
+    /// Call `callback` with (step, node_index, from, to) for each stretch of
+    /// a node covered by an aligned span, in the node's forward coordinates
+    ///
+    /// # Arguments
+    /// * `graph` - Graph used to resolve step names and node lengths
+    /// * `ops` - Alignment operations restricting coverage to matched
+    ///   bases, or `None` to cover the whole target range
+    /// * `callback` - Function called with the 0-based position of the step
+    ///   along the path, its node and the covered `[from, to)` range
+    pub fn for_each_covered_interval(
+        &self,
+        graph: &GraphLengths,
+        ops: Option<&[AlignOp]>,
+        mut callback: impl FnMut(usize, usize, usize, usize),
+    ) -> Result<(), RecordError> {
+        if self.is_unmapped() {
+            return Ok(());
+        }
+        let steps = self.layout(graph)?;
+        let mut i = 0;
+        for (span_start, span_end) in self.aligned_spans(ops) {
+            // Spans are sorted, so earlier steps never need revisiting
+            while i < steps.len() && steps[i].start + steps[i].len <= span_start {
+                i += 1;
+            }
+            for (n, step) in steps.iter().enumerate().skip(i) {
+                if step.start >= span_end {
+                    break;
+                }
+                let from = span_start.max(step.start) - step.start;
+                let to = span_end.min(step.start + step.len) - step.start;
+                if from >= to {
+                    continue;
+                }
+                if step.reverse {
+                    callback(n, step.index, step.len - to, step.len - from);
+                } else {
+                    callback(n, step.index, from, to);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Call `callback` with each pair of consecutive steps whose junction
+    /// lies inside the aligned target range
+    pub fn for_each_edge(
+        &self,
+        graph: &GraphLengths,
+        mut callback: impl FnMut(&PathStep, &PathStep),
+    ) -> Result<(), RecordError> {
+        if self.is_unmapped() {
+            return Ok(());
+        }
+        let steps = self.layout(graph)?;
+        for pair in steps.windows(2) {
+            let junction = pair[1].start;
+            if junction > self.path_start && junction < self.path_end {
+                callback(&pair[0], &pair[1]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the record against the graph, returning every problem found
+    ///
+    /// Reports path steps missing from the graph, a path length (column 7)
+    /// that differs from the summed node lengths, and target coordinates
+    /// outside the path or not starting in its first node and ending in its
+    /// last, i.e. everything that makes coverage reject the record. With
+    /// `use_cigar`, a malformed `cg`/`cs` tag or one not consuming exactly
+    /// the target span is reported too.
+    pub fn check_against(&self, graph: &GraphLengths, use_cigar: bool) -> Vec<RecordError> {
+        let mut problems = Vec::new();
+        if self.is_unmapped() {
+            return problems;
+        }
+        let mut lengths = Some(Vec::new());
+        for name in self.steps() {
+            match graph.index_of(name) {
+                Some(i) => {
+                    if let Some(lengths) = &mut lengths {
+                        lengths.push(graph.node_len(i));
+                    }
+                }
+                None => {
+                    problems.push(RecordError::new("path", name, "node not in graph"));
+                    lengths = None;
+                }
+            }
+        }
+        if let Some(lengths) = &lengths {
+            let path_len: usize = lengths.iter().sum();
+            if path_len != self.path_len {
+                problems.push(RecordError::new(
+                    "path_len",
+                    self.path_len.to_string(),
+                    format!("path nodes sum to {} bp in the graph", path_len),
+                ));
+            }
+        }
+        // Without the node lengths, check the span against column 7 alone
+        let lengths = lengths.unwrap_or_else(|| vec![self.path_len]);
+        problems.extend(self.span_problems(&lengths));
+        if use_cigar {
+            match self.alignment_ops() {
+                Ok(Some(ops)) => problems.extend(self.check_ops(&ops).err()),
+                Ok(None) => {}
+                Err(problem) => problems.push(problem),
+            }
+        }
+        problems
+    }
+
+    /// Resolve the path steps to dense node indices
+    pub fn step_indices(&self, graph: &GraphLengths) -> Result<Vec<usize>, RecordError> {
+        self.steps()
+            .map(|name| {
+                graph
+                    .index_of(name)
+                    .ok_or_else(|| RecordError::new("path", name, "node not in graph"))
+            })
+            .collect()
+    }
+
+    /// Process each step in the alignment, calculating coverage for graph nodes
+    ///
+    /// # Arguments
+    /// * `graph` - Graph used to resolve step names and node lengths
+    /// * `callback` - Function called for each node with (node_index, coverage_length)
+    ///
+    /// # Details
+    /// - Handles both forward (>) and reverse (<) node traversals
+    /// - Adjusts coverage for partial node alignments at path ends
+    /// - Accumulates coverage across multi-node paths
+    ///
+    /// The whole walk is checked before `callback` is first called, so an
+    /// inconsistent record credits no node.
+    pub fn for_each_step(
+        &self,
+        graph: &GraphLengths,
+        mut callback: impl FnMut(usize, usize),
+    ) -> Result<(), RecordError> {
+        if self.is_unmapped() {
+            return Ok(());
+        }
+        let nodes = self.step_indices(graph)?;
+        let lengths = nodes.iter().map(|&n| graph.node_len(n)).collect::<Vec<_>>();
+        self.check_span(&lengths)?;
+        // The target span starts inside the first node and ends inside the
+        // last one, so only those two are partially covered
+        let target_len = self.path_end - self.path_start;
+        let last = nodes.len().saturating_sub(1);
+        let mut seen: usize = 0;
+        for (i, (node, mut len)) in nodes.into_iter().zip(lengths).enumerate() {
+            if i == 0 {
+                len -= self.path_start;
+            }
+            if i == last {
+                len = target_len - seen;
+            }
+            seen += len;
+            callback(node, len);
+        }
+        Ok(())
+    }
+
+    /// Problems of the target span on a path whose steps have the given
+    /// node lengths
+    fn span_problems(&self, lengths: &[usize]) -> Vec<RecordError> {
+        let mut problems = Vec::new();
+        let path_len: usize = lengths.iter().sum();
+        if self.path_start > self.path_end {
+            problems.push(RecordError::new(
+                "path_start",
+                self.path_start.to_string(),
+                format!("greater than path_end {}", self.path_end),
+            ));
+        }
+        if let Some(&first) = lengths.first() {
+            if self.path_start > first {
+                problems.push(RecordError::new(
+                    "path_start",
+                    self.path_start.to_string(),
+                    format!("beyond the end of the first node ({} bp)", first),
+                ));
+            }
+        }
+        if let Some(&last) = lengths.last() {
+            if self.path_end < path_len - last {
+                problems.push(RecordError::new(
+                    "path_end",
+                    self.path_end.to_string(),
+                    "ends before the last node of the path",
+                ));
+            }
+        }
+        if self.path_end > path_len {
+            problems.push(RecordError::new(
+                "path_end",
+                self.path_end.to_string(),
+                format!("beyond the end of the path ({} bp)", path_len),
+            ));
+        }
+        problems
+    }
+
+    /// Check that the target span lies on a path whose steps have the given
+    /// node lengths, starting in the first node and ending in the last
+    pub fn check_span(&self, lengths: &[usize]) -> Result<(), RecordError> {
+        match self.span_problems(lengths).into_iter().next() {
+            Some(problem) => Err(problem),
+            None => Ok(()),
+        }
+    }
+
+    /// Check that alignment operations consume exactly the target span
+    pub fn check_ops(&self, ops: &[AlignOp]) -> Result<(), RecordError> {
+        let consumed: usize = ops
+            .iter()
+            .map(|op| match *op {
+                AlignOp::Match(len) | AlignOp::Deletion(len) => len,
+                AlignOp::Insertion(_) => 0,
+            })
+            .sum();
+        let span = self.path_end.saturating_sub(self.path_start);
+        if consumed == span {
+            return Ok(());
+        }
+        let field = if self.tag("cg").is_some() { "cg" } else { "cs" };
+        Err(RecordError::new(
+            field,
+            self.tag(field).unwrap_or_default(),
+            format!(
+                "covers {} target bases but path_start..path_end spans {}",
+                consumed, span
+            ),
+        ))
+    }
+
+    /// Walk alignment operations along the path, splitting each node's
+    /// aligned span into matched and deleted target bases
+    ///
+    /// # Arguments
+    /// * `graph` - Graph used to resolve step names and node lengths
+    /// * `ops` - Alignment operations, e.g. from [`GafRecord::alignment_ops`]
+    /// * `callback` - Function called for each node with (node_index, matched, deleted)
+    ///
+    /// # Details
+    /// Operations are laid out from `path_start`; insertions consume no
+    /// target bases and so credit no node. The record is rejected if the
+    /// target span does not fit the path or the operations do not consume
+    /// exactly `path_end - path_start` target bases.
+    pub fn for_each_step_with_ops(
+        &self,
+        graph: &GraphLengths,
+        ops: &[AlignOp],
+        mut callback: impl FnMut(usize, usize, usize),
+    ) -> Result<(), RecordError> {
+        if self.is_unmapped() {
+            return Ok(());
+        }
+        let nodes = self.step_indices(graph)?;
+        let lengths = nodes.iter().map(|&n| graph.node_len(n)).collect::<Vec<_>>();
+        self.check_span(&lengths)?;
+        self.check_ops(ops)?;
+        let mut counts = vec![(0, 0); nodes.len()];
+        let mut i = 0;
+        let mut node_end = nodes.first().map_or(0, |&n| graph.node_len(n));
+        let mut pos = self.path_start;
+        for op in ops {
+            let (mut remaining, deleted) = match *op {
+                AlignOp::Match(len) => (len, false),
+                AlignOp::Deletion(len) => (len, true),
+                AlignOp::Insertion(_) => continue,
+            };
+            while remaining > 0 {
+                while i < nodes.len() && pos >= node_end {
+                    i += 1;
+                    if let Some(&n) = nodes.get(i) {
+                        node_end += graph.node_len(n);
+                    }
+                }
+                if i == nodes.len() {
+                    break;
+                }
+                let take = remaining.min(node_end - pos);
+                if deleted {
+                    counts[i].1 += take;
+                } else {
+                    counts[i].0 += take;
+                }
+                pos += take;
+                remaining -= take;
+            }
+        }
+        for (n, (matched, deleted)) in nodes.into_iter().zip(counts) {
+            callback(n, matched, deleted);
+        }
+        Ok(())
+    }
+}
+
+/// Split a `>a<b` walk into segment names and orientations, `true` for a
+/// reverse (`<`) step; a name without a leading orientation is forward
+pub(crate) fn split_walk(path: &str) -> Vec<(&str, bool)> {
+    let mut steps = Vec::new();
+    let mut reverse = false;
+    let mut start = 0;
+    for (i, c) in path.char_indices() {
+        if c == '<' || c == '>' {
+            if i > start {
+                steps.push((&path[start..i], reverse));
+            }
+            reverse = c == '<';
+            start = i + 1;
+        }
+    }
+    if start < path.len() {
+        steps.push((&path[start..], reverse));
+    }
+    steps
+}
+
+/// Name, start and end on the query of a raw GAF line, which identify its
+/// query group (see [`GafRecord::query_key`]), read without parsing the
+/// other columns; `None` if they are missing or malformed
+pub fn query_columns(line: &str) -> Option<(&str, usize, usize)> {
+    let mut fields = line.split('\t');
+    let name = fields.next()?;
+    let _query_len = fields.next()?;
+    let start = parse_num("query_start", fields.next()?).ok()?;
+    let end = parse_num("query_end", fields.next()?).ok()?;
+    Some((name, start, end))
+}
+
+/// Parse a numeric GAF column, reading `*` as 0
+fn parse_num(field: &'static str, value: &str) -> Result<usize, RecordError> {
+    if value == "*" {
+        return Ok(0);
+    }
+    value
+        .parse::<usize>()
+        .map_err(|_| RecordError::new(field, value, "not a non-negative integer"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coverage::{Coverage, CoverageOptions};
+
+    fn graph() -> GraphLengths {
+        let segments = [("1", 4), ("2", 3), ("3", 5)]
+            .iter()
+            .map(|(name, len)| (name.to_string(), *len))
+            .collect();
+        GraphLengths::from_segments(segments, Default::default())
+    }
+
+    /// (from, to) node indices of the junctions `for_each_edge` reports
+    fn edges(line: &str) -> Vec<(usize, usize)> {
+        let record = GafRecord::parse(line).unwrap();
+        let mut edges = Vec::new();
+        record
+            .for_each_edge(&graph(), |from, to| edges.push((from.index, to.index)))
+            .unwrap();
+        edges
+    }
+
+    #[test]
+    fn edges_inside_the_target_range() {
+        // Every junction strictly inside path_start..path_end
+        assert_eq!(
+            edges("r\t12\t0\t12\t+\t>1>2>3\t12\t0\t12\t12\t12\t60"),
+            [(0, 1), (1, 2)]
+        );
+        assert_eq!(edges("r\t7\t0\t7\t+\t<2<1\t7\t2\t5\t3\t3\t60"), [(1, 0)]);
+        // A junction at path_start or path_end is not traversed
+        assert_eq!(
+            edges("r\t8\t0\t8\t+\t>1>2>3\t12\t4\t12\t8\t8\t60"),
+            [(1, 2)]
+        );
+        assert_eq!(edges("r\t7\t0\t7\t+\t>1>2>3\t12\t0\t7\t7\t7\t60"), [(0, 1)]);
+    }
+
+    #[test]
+    fn edge_coverage_follows_links() {
+        let mut graph = graph();
+        let gfa = "L\t1\t+\t2\t+\t0M\n";
+        graph.read_links("graph.gfa", gfa.as_bytes()).unwrap();
+        let options = CoverageOptions {
+            edges: true,
+            ..Default::default()
+        };
+        let mut coverage = Coverage::with_options(&graph, options);
+        for line in [
+            // Reverse complement of the link
+            "r1\t7\t0\t7\t+\t<2<1\t7\t0\t7\t7\t7\t60",
+            // The link, then a junction no L line joins
+            "r2\t12\t0\t12\t+\t>1>2>3\t12\t0\t12\t12\t12\t60",
+            // Starts on the link's junction
+            "r3\t8\t0\t8\t+\t>1>2\t7\t4\t7\t3\t3\t60",
+        ] {
+            let record = GafRecord::parse(line).unwrap();
+            coverage.add_record(&graph, &record, 1.0).unwrap();
+        }
+        assert_eq!(coverage.edges(), Some(&[2.0][..]));
+        assert_eq!(coverage.unlinked(), 1);
+    }
+
+    #[test]
+    fn malformed_alignment_tags_are_errors() {
+        let graph = graph();
+        let cigar = CoverageOptions {
+            use_cigar: true,
+            ..Default::default()
+        };
+        for tag in ["cg:Z:10Q", "cs:Z::4-"] {
+            let line = format!("r\t10\t0\t10\t+\t>1>2>3\t12\t0\t10\t10\t10\t60\t{}", tag);
+            let record = GafRecord::parse(&line).unwrap();
+            let field = &tag[..2];
+            assert_eq!(record.alignment_ops().unwrap_err().field, field);
+
+            // Coverage rejects the record rather than crediting its span
+            let mut coverage = Coverage::with_options(&graph, cigar.clone());
+            let error = coverage.add_record(&graph, &record, 1.0).unwrap_err();
+            assert_eq!(error.field, field);
+            assert_eq!(coverage.values(), [0.0; 3]);
+
+            let problems = record.check_against(&graph, true);
+            assert_eq!(problems.len(), 1);
+            assert_eq!(problems[0].field, field);
+            assert!(record.check_against(&graph, false).is_empty());
+        }
+
+        // Without either tag the whole span is credited
+        let line = "r\t10\t0\t10\t+\t>1>2>3\t12\t0\t10\t10\t10\t60";
+        let record = GafRecord::parse(line).unwrap();
+        assert_eq!(record.alignment_ops(), Ok(None));
+        let mut coverage = Coverage::with_options(&graph, cigar);
+        coverage.add_record(&graph, &record, 1.0).unwrap();
+        assert_eq!(coverage.values(), [4.0, 3.0, 3.0]);
+    }
+}
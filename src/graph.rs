@@ -0,0 +1,414 @@
+use crate::error::Error;
+use crate::io::open_input;
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::io::prelude::*;
+
+/// Column layout for graphs with integer segment names
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IdLayout {
+    /// One slot per segment that exists in the GFA
+    #[default]
+    Compact,
+    /// One slot per ID between the smallest and largest, filling gaps with
+    /// zero-length placeholder nodes
+    Dense,
+}
+
+/// A GFA link (L line) between two oriented nodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Link {
+    pub from: usize,
+    pub from_reverse: bool,
+    pub to: usize,
+    pub to_reverse: bool,
+}
+
+impl Link {
+    /// Key shared by a link and its reverse complement, so that `a+ b+`
+    /// and `b- a-` are found as the same link
+    fn key(&self) -> (usize, bool, usize, bool) {
+        let forward = (self.from, self.from_reverse, self.to, self.to_reverse);
+        let reverse = (self.to, !self.to_reverse, self.from, !self.from_reverse);
+        forward.min(reverse)
+    }
+}
+
+/// Integer ID of a segment name written canonically, without a sign or
+/// leading zeros, so that e.g. `01` and `+1` stay distinct names from `1`
+fn numeric_id(name: &str) -> Option<usize> {
+    name.parse::<usize>()
+        .ok()
+        .filter(|id| id.to_string() == name)
+}
+
+/// How segment names map onto dense indices
+#[derive(Debug, Clone)]
+enum NodeIndex {
+    /// Integer names covering `min_id..min_id + len`: index is `id - min_id`.
+    /// `present` marks the IDs that exist when the range has gaps.
+    Numeric {
+        min_id: usize,
+        present: Option<Vec<bool>>,
+    },
+    /// Sorted integer names with gaps, looked up by binary search
+    Sparse { ids: Vec<usize> },
+    /// Arbitrary names, indexed in the order of their S lines
+    Named {
+        names: Vec<String>,
+        index: HashMap<String, usize>,
+    },
+}
+
+/// Segment lengths of a GFA graph, indexed densely by node
+///
+/// Graphs whose segment names are all integers are indexed in ID order,
+/// using `id - min_id` directly when the IDs are contiguous; any other
+/// naming falls back to a name lookup table built from the S lines.
+#[derive(Debug, Clone)]
+pub struct GraphLengths {
+    segment_lengths: Vec<usize>,
+    nodes: NodeIndex,
+    /// L lines in file order, loaded on demand by [`GraphLengths::load_links`]
+    links: Vec<Link>,
+    link_index: HashMap<(usize, bool, usize, bool), usize>,
+}
+
+impl GraphLengths {
+    /// Parse GFA file and extract segment information
+    ///
+    /// The file may be compressed in any format [`open_input`] detects;
+    /// BGZF input is decompressed on `threads` threads.
+    pub fn from_gfa(gfa_path: &str, layout: IdLayout, threads: usize) -> Result<Self, Error> {
+        let mut reader = open_input(gfa_path, threads)?;
+        let mut line = String::new();
+        let mut segments = Vec::new();
+
+        loop {
+            line.clear();
+            let bytes_read = reader
+                .read_line(&mut line)
+                .map_err(|e| Error::io(gfa_path, e))?;
+            if bytes_read == 0 {
+                break;
+            }
+
+            let line_str = line.trim();
+
+            // Only process segment lines
+            if !line_str.starts_with('S') {
+                continue;
+            }
+
+            // Parse segment line format: S<tab>id<tab>sequence
+            let mut fields = line_str.split('\t');
+            let Some((id_str, seq)) = fields.next().and_then(|_type| {
+                let id_str = fields.next()?;
+                let seq = fields.next()?;
+                Some((id_str, seq))
+            }) else {
+                continue;
+            };
+
+            segments.push((id_str.to_string(), seq.len()));
+        }
+
+        Ok(Self::from_segments(segments, layout))
+    }
+
+    /// Build the index from (name, length) pairs in S-line order
+    pub fn from_segments(segments: Vec<(String, usize)>, layout: IdLayout) -> Self {
+        let numeric_ids = segments
+            .iter()
+            .map(|(name, _)| numeric_id(name))
+            .collect::<Option<Vec<usize>>>();
+
+        if let Some(ids) = numeric_ids.filter(|ids| !ids.is_empty()) {
+            // Sort by ID, letting the last S line win for duplicated IDs
+            let mut by_id = ids
+                .into_iter()
+                .zip(segments.into_iter().map(|(_, len)| len))
+                .collect::<Vec<(usize, usize)>>();
+            by_id.sort_by_key(|(id, _)| *id);
+            let mut ids: Vec<usize> = Vec::with_capacity(by_id.len());
+            let mut lengths: Vec<usize> = Vec::with_capacity(by_id.len());
+            for (id, len) in by_id {
+                if ids.last() == Some(&id) {
+                    *lengths.last_mut().unwrap() = len;
+                } else {
+                    ids.push(id);
+                    lengths.push(len);
+                }
+            }
+
+            let min_id = ids[0];
+            let max_id = ids[ids.len() - 1];
+            let span = max_id - min_id + 1;
+            if ids.len() == span {
+                return Self::with_nodes(
+                    lengths,
+                    NodeIndex::Numeric {
+                        min_id,
+                        present: None,
+                    },
+                );
+            }
+            return match layout {
+                IdLayout::Compact => Self::with_nodes(lengths, NodeIndex::Sparse { ids }),
+                IdLayout::Dense => {
+                    // Create a dense vector for O(1) access
+                    let mut segment_lengths = vec![0; span];
+                    let mut present = vec![false; span];
+                    for (id, len) in ids.into_iter().zip(lengths) {
+                        segment_lengths[id - min_id] = len;
+                        present[id - min_id] = true;
+                    }
+                    Self::with_nodes(
+                        segment_lengths,
+                        NodeIndex::Numeric {
+                            min_id,
+                            present: Some(present),
+                        },
+                    )
+                }
+            };
+        }
+
+        let mut names = Vec::with_capacity(segments.len());
+        let mut index = HashMap::with_capacity(segments.len());
+        let mut segment_lengths = Vec::with_capacity(segments.len());
+        for (name, len) in segments {
+            if let Some(&i) = index.get(&name) {
+                segment_lengths[i] = len;
+                continue;
+            }
+            index.insert(name.clone(), names.len());
+            names.push(name);
+            segment_lengths.push(len);
+        }
+        Self::with_nodes(segment_lengths, NodeIndex::Named { names, index })
+    }
+
+    fn with_nodes(segment_lengths: Vec<usize>, nodes: NodeIndex) -> Self {
+        Self {
+            segment_lengths,
+            nodes,
+            links: Vec::new(),
+            link_index: HashMap::new(),
+        }
+    }
+
+    /// Read the L lines of a GFA file, replacing any links already loaded
+    ///
+    /// Links whose segments are not in the graph, or with an orientation
+    /// other than `+`/`-`, are ignored. A link listed twice (including as
+    /// its reverse complement) keeps its first L line.
+    pub fn load_links(&mut self, gfa_path: &str, threads: usize) -> Result<(), Error> {
+        let reader = open_input(gfa_path, threads)?;
+        self.read_links(gfa_path, reader)
+    }
+
+    /// Read the L lines of an opened GFA, named `gfa_path` in errors
+    pub(crate) fn read_links(
+        &mut self,
+        gfa_path: &str,
+        mut reader: impl BufRead,
+    ) -> Result<(), Error> {
+        let mut line = String::new();
+        self.links.clear();
+        self.link_index.clear();
+
+        loop {
+            line.clear();
+            let bytes_read = reader
+                .read_line(&mut line)
+                .map_err(|e| Error::io(gfa_path, e))?;
+            if bytes_read == 0 {
+                break;
+            }
+
+            // Parse link line format: L<tab>from<tab>orient<tab>to<tab>orient
+            let line_str = line.trim();
+            if !line_str.starts_with('L') {
+                continue;
+            }
+            let fields = line_str.split('\t').collect::<Vec<_>>();
+            let Some(link) = self.parse_link(&fields) else {
+                continue;
+            };
+
+            if let Entry::Vacant(entry) = self.link_index.entry(link.key()) {
+                entry.insert(self.links.len());
+                self.links.push(link);
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolve the fields of an L line, if its segments are in the graph
+    fn parse_link(&self, fields: &[&str]) -> Option<Link> {
+        let orientation = |field: &str| match field {
+            "+" => Some(false),
+            "-" => Some(true),
+            _ => None,
+        };
+        Some(Link {
+            from: self.index_of(fields.get(1)?)?,
+            from_reverse: orientation(fields.get(2)?)?,
+            to: self.index_of(fields.get(3)?)?,
+            to_reverse: orientation(fields.get(4)?)?,
+        })
+    }
+
+    /// Number of node slots, i.e. the size of the dense coverage vector
+    pub fn len(&self) -> usize {
+        self.segment_lengths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segment_lengths.is_empty()
+    }
+
+    /// Dense index of a segment name, if it is in the graph (gap
+    /// placeholders of the dense layout are not)
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        match &self.nodes {
+            NodeIndex::Numeric { min_id, present } => numeric_id(name)
+                .and_then(|id| id.checked_sub(*min_id))
+                .filter(|i| *i < self.segment_lengths.len())
+                // Gap placeholders of the dense layout are not segments
+                .filter(|i| present.as_ref().is_none_or(|present| present[*i])),
+            NodeIndex::Sparse { ids } => {
+                let id = numeric_id(name)?;
+                ids.binary_search(&id).ok()
+            }
+            NodeIndex::Named { index, .. } => index.get(name).copied(),
+        }
+    }
+
+    /// Segment name of a dense index
+    pub fn node_name(&self, index: usize) -> Cow<'_, str> {
+        match &self.nodes {
+            NodeIndex::Numeric { min_id, .. } => Cow::Owned((min_id + index).to_string()),
+            NodeIndex::Sparse { ids } => Cow::Owned(ids[index].to_string()),
+            NodeIndex::Named { names, .. } => Cow::Borrowed(&names[index]),
+        }
+    }
+
+    /// Whether the slot at the given index is a real segment rather than a
+    /// gap placeholder of the dense layout
+    pub fn is_present(&self, index: usize) -> bool {
+        match &self.nodes {
+            NodeIndex::Numeric {
+                present: Some(present),
+                ..
+            } => present[index],
+            _ => index < self.len(),
+        }
+    }
+
+    /// Length of the node at the given dense index
+    pub fn node_len(&self, index: usize) -> usize {
+        self.segment_lengths[index]
+    }
+
+    /// Segment lengths in dense index order
+    pub fn segment_lengths(&self) -> &[usize] {
+        &self.segment_lengths
+    }
+
+    /// Loaded links, in L-line order
+    pub fn links(&self) -> &[Link] {
+        &self.links
+    }
+
+    /// Index of the link joining two oriented nodes, in either direction
+    pub fn link_between(
+        &self,
+        from: usize,
+        from_reverse: bool,
+        to: usize,
+        to_reverse: bool,
+    ) -> Option<usize> {
+        let link = Link {
+            from,
+            from_reverse,
+            to,
+            to_reverse,
+        };
+        self.link_index.get(&link.key()).copied()
+    }
+
+    /// Segment names in dense index order
+    pub fn node_names(&self) -> impl Iterator<Item = Cow<'_, str>> {
+        (0..self.len()).map(|i| self.node_name(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(names: &[&str], layout: IdLayout) -> GraphLengths {
+        let segments = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.to_string(), i + 1))
+            .collect();
+        GraphLengths::from_segments(segments, layout)
+    }
+
+    fn names(graph: &GraphLengths) -> Vec<String> {
+        graph.node_names().map(|n| n.into_owned()).collect()
+    }
+
+    #[test]
+    fn numeric_names_are_sorted_by_id() {
+        let g = graph(&["3", "1", "2"], IdLayout::Compact);
+        assert_eq!(names(&g), ["1", "2", "3"]);
+        assert_eq!(g.index_of("3"), Some(2));
+        assert_eq!(g.node_len(2), 1);
+        assert_eq!(g.index_of("03"), None);
+        assert_eq!(g.index_of("+3"), None);
+    }
+
+    #[test]
+    fn leading_zeros_and_signs_are_names() {
+        let g = graph(&["1", "01", "+5"], IdLayout::Compact);
+        assert_eq!(names(&g), ["1", "01", "+5"]);
+        assert_eq!(g.index_of("1"), Some(0));
+        assert_eq!(g.index_of("01"), Some(1));
+        assert_eq!(g.index_of("+5"), Some(2));
+        assert_eq!(g.index_of("5"), None);
+        assert_eq!(g.node_len(1), 2);
+    }
+
+    #[test]
+    fn links_match_their_reverse_complement() {
+        let mut g = graph(&["1", "2", "3"], IdLayout::Compact);
+        let gfa = "L\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t-\t0M\nL\t2\t-\t1\t-\t0M\nL\t1\t+\t4\t+\t0M\n";
+        g.read_links("graph.gfa", gfa.as_bytes()).unwrap();
+        // The third line is the first one's reverse complement, and the
+        // last one names a missing segment
+        assert_eq!(g.links().len(), 2);
+
+        // 1+ 2+, also found walking <2<1
+        assert_eq!(g.link_between(0, false, 1, false), Some(0));
+        assert_eq!(g.link_between(1, true, 0, true), Some(0));
+        // 2+ 3-, also found walking >3<2
+        assert_eq!(g.link_between(1, false, 2, true), Some(1));
+        assert_eq!(g.link_between(2, false, 1, true), Some(1));
+        // Orientations must match too
+        assert_eq!(g.link_between(0, false, 1, true), None);
+        assert_eq!(g.link_between(1, false, 0, false), None);
+    }
+
+    #[test]
+    fn mixed_names_keep_s_line_order() {
+        let g = graph(&["10", "s2", "chr1_node5", "3"], IdLayout::Dense);
+        assert_eq!(names(&g), ["10", "s2", "chr1_node5", "3"]);
+        assert_eq!(g.index_of("3"), Some(3));
+        assert_eq!(g.index_of("s3"), None);
+    }
+}
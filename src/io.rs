@@ -0,0 +1,61 @@
+use crate::bgzf::{is_bgzf, ParallelBgzfReader};
+use crate::error::Error;
+use std::fs::File;
+use std::io::{prelude::*, BufReader, Cursor};
+
+/// Path that stands for standard input
+pub const STDIN: &str = "-";
+
+/// Number of leading bytes inspected to detect the compression format
+const MAGIC_LEN: usize = 5;
+
+/// Open a file, or standard input for `-`, decompressing it if needed
+///
+/// The compression format is detected from the content rather than the file
+/// name: gzip, bgzip, zstd, xz and bzip2 are supported, and input shorter
+/// than any of their headers is read uncompressed. BGZF input is
+/// decompressed on `threads` threads when more than one is given.
+pub fn open_input(filename: &str, threads: usize) -> Result<Box<dyn BufRead>, Error> {
+    let input: Box<dyn Read> = if filename == STDIN {
+        Box::new(std::io::stdin().lock())
+    } else {
+        Box::new(File::open(filename).map_err(|e| Error::io(filename, e))?)
+    };
+    let mut input = BufReader::new(input);
+    if threads > 1 && is_bgzf(input.fill_buf().map_err(|e| Error::io(filename, e))?) {
+        return Ok(Box::new(BufReader::new(ParallelBgzfReader::new(
+            input, threads,
+        ))));
+    }
+    // Inputs too short to hold a compression header (such as an empty
+    // GAF) are read as they are
+    let mut head = Vec::with_capacity(MAGIC_LEN);
+    (&mut input)
+        .take(MAGIC_LEN as u64)
+        .read_to_end(&mut head)
+        .map_err(|e| Error::io(filename, e))?;
+    if head.len() < MAGIC_LEN {
+        return Ok(Box::new(Cursor::new(head)));
+    }
+    let (reader, _compression) = niffler::get_reader(Box::new(Cursor::new(head).chain(input)))
+        .map_err(|e| Error::io(filename, std::io::Error::other(e)))?;
+    Ok(Box::new(BufReader::new(reader)))
+}
+
+/// Iterates through each line in a file, applying the provided callback function
+///
+/// # Arguments
+/// * `filename` - Path to the file to read, or `-` for standard input
+/// * `callback` - Function to call for each line with (line_number, line),
+///   counting lines from 1; an error stops the iteration
+pub fn for_each_line_in_file(
+    filename: &str,
+    mut callback: impl FnMut(usize, &str) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let buf_reader = open_input(filename, 1)?;
+    for (i, line) in buf_reader.lines().enumerate() {
+        let line = line.map_err(|e| Error::io(filename, e))?;
+        callback(i + 1, &line)?;
+    }
+    Ok(())
+}
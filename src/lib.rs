@@ -0,0 +1,38 @@
+//! Calculate node coverage from GAF alignments to GFA variation graphs.
+//!
+//! The library exposes the pieces used by the `gafpack` binary so coverage
+//! can be computed in-process:
+//! - [`GraphLengths`] indexes the segment lengths of a GFA graph
+//! - [`GafRecord`] is a parsed GAF alignment line
+//! - [`Coverage`] accumulates per-node coverage from records
+//! - [`RecordFilter`] decides which records contribute coverage
+//! - [`pack_gaf`] computes the coverage of a whole GAF file
+//! - [`GraphPath`] is a path or walk of the graph, read with [`for_each_path`]
+
+pub mod bgzf;
+pub mod cigar;
+pub mod coverage;
+pub mod depth;
+pub mod error;
+pub mod filter;
+pub mod gaf;
+pub mod graph;
+pub mod io;
+pub mod output;
+pub mod pack;
+pub mod parallel;
+pub mod paths;
+pub mod validate;
+
+pub use cigar::AlignOp;
+pub use coverage::{CountMode, Coverage, CoverageOptions, MinOverlap};
+pub use depth::BaseDepth;
+pub use error::{Error, ErrorPolicy, RecordError};
+pub use filter::{FilterReason, FilterStats, RecordFilter};
+pub use gaf::{GafRecord, PathStep};
+pub use graph::{GraphLengths, IdLayout, Link};
+pub use io::{for_each_line_in_file, open_input, STDIN};
+pub use output::SampleCoverage;
+pub use pack::{pack_gaf, PackConfig, WeightMode};
+pub use paths::{for_each_path, GraphPath, PathSummary, PathWindow};
+pub use validate::{validate_gaf, ValidationSummary};
@@ -1,253 +1,529 @@
-use clap::Parser;
-use flate2::read::GzDecoder;
-use std::collections::HashMap;
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use gafpack::output::{
+    default_sample_name, track_labels, write_base_depth, write_base_depth_header, write_bedgraph,
+    write_columns, write_edge_header, write_edge_rows, write_path_summary_header,
+    write_path_summary_rows, write_sparse_header, write_sparse_rows, write_tabular_header,
+    write_tabular_row, write_window_header, write_window_rows,
+};
+use gafpack::{
+    for_each_path, pack_gaf, validate_gaf, CountMode, CoverageOptions, Error, ErrorPolicy,
+    FilterStats, GraphLengths, GraphPath, IdLayout, MinOverlap, PackConfig, RecordError,
+    RecordFilter, SampleCoverage, WeightMode, STDIN,
+};
 use std::fs::File;
-use std::io::{prelude::*, BufReader};
-use std::path::Path;
+use std::io::{self, prelude::*, BufReader, BufWriter};
 
-/// Iterates through each line in a file, applying the provided callback function
-///
-/// # Arguments
-/// * `filename` - Path to the file to read
-/// * `callback` - Function to call for each line
-fn for_each_line_in_file(filename: &str, mut callback: impl FnMut(&str)) {
-    let file = File::open(filename).unwrap();
-    let (reader, _compression) = niffler::get_reader(Box::new(file)).unwrap();
-    let buf_reader = BufReader::new(reader);
-    for line in buf_reader.lines() {
-        callback(&line.unwrap());
-    }
-}
-
-/// Process each step in a GAF alignment line, calculating coverage for graph nodes
-///
-/// # Arguments
-/// * `line` - A GAF format alignment line
-/// * `callback` - Function called for each node with (node_id, coverage_length)
-/// * `get_node_len` - Function to get the length of a node by its ID
-///
-/// # Details
-/// Parses GAF alignment lines to extract node coverage information:
-/// - Handles both forward (>) and reverse (<) node traversals
-/// - Adjusts coverage for partial node alignments at path ends
-/// - Accumulates coverage across multi-node paths
-fn for_each_step(
-    line: &str,
-    mut callback: impl FnMut(usize, usize),
-    mut get_node_len: impl FnMut(usize) -> usize,
-) {
-    //eprintln!("{}", line);
-    let walk = line.split('\t').nth(5).unwrap();
-    if walk != "*" {
-        //eprintln!("oheunotoeunthoue");
-        let target_start = line.split('\t').nth(7).unwrap().parse::<usize>().unwrap();
-        let target_end = line.split('\t').nth(8).unwrap().parse::<usize>().unwrap();
-        let target_len = target_end - target_start;
-        //eprintln!("target_len = {}", target_len);
-        let fields = line
-            .split('\t')
-            .nth(5)
-            .unwrap()
-            .split(|c| c == '<' || c == '>')
-            .filter(|s| !s.is_empty())
-            .map(|s| s.parse::<usize>().unwrap())
-            .enumerate()
-            .collect::<Vec<(usize, usize)>>();
-        let mut seen: usize = 0;
-        let fields_len = fields.as_slice().len();
-        //eprintln!("fields len = {}", fields_len);
-        for (i, j) in fields {
-            let mut len = get_node_len(j);
-            //eprintln!("node {} len = {}", j, len);
-            if i == 0 {
-                //eprintln!("on first step {} {} {}", len, target_start, seen);
-                assert!(len >= target_start);
-                len -= target_start;
-            }
-            if i == fields_len - 1 {
-                //eprintln!("on last step {} {} {}", len, target_end, seen);
-                assert!(target_len >= seen);
-                len = target_len - seen;
-            }
-            if i == fields_len {
-                assert!(false);
-            }
-            //eprintln!("node {} adj len = {}", j, len);
-            seen += len;
-            callback(j, len);
-        }
-        //eprintln!("seen = {}", seen);
-    }
-    //eprintln!("at end");
-}
-
-/// Create a reader that handles compressed files
-fn create_reader(path: &Path) -> std::io::Result<Box<dyn BufRead>> {
-    let file = File::open(path)?;
-
-    if path
-        .extension()
-        .is_some_and(|ext| ext == "gz" || ext == "bgz")
-    {
-        let decoder = GzDecoder::new(file);
-        let buf_reader = BufReader::new(decoder);
-        Ok(Box::new(buf_reader))
-    } else {
-        let buf_reader = BufReader::new(file);
-        Ok(Box::new(buf_reader))
-    }
-}
-
-/// Parse GFA file and extract segment information
-/// Returns (segment_lengths, min_id) where segment_lengths[id - min_id] gives the length
-fn parse_gfa(gfa_path: &str) -> std::io::Result<(Vec<usize>, usize)> {
-    let path = Path::new(gfa_path);
-    let mut reader = create_reader(path)?;
-    let mut line = String::new();
-    let mut segments_map = HashMap::new();
-    let mut min_id = usize::MAX;
-    let mut max_id = 0;
-
-    loop {
-        line.clear();
-        let bytes_read = reader.read_line(&mut line)?;
-        if bytes_read == 0 {
-            break;
-        }
-
-        let line_str = line.trim();
-
-        // Only process segment lines
-        if !line_str.starts_with('S') {
-            continue;
-        }
-
-        // Parse segment line format: S<tab>id<tab>sequence
-        let mut fields = line_str.split('\t');
-        let Some((id_str, seq)) = fields.next().and_then(|_type| {
-            let id_str = fields.next()?;
-            let seq = fields.next()?;
-            Some((id_str, seq))
-        }) else {
-            continue;
-        };
-
-        // Parse segment ID
-        let id = id_str.parse::<usize>().unwrap();
-        min_id = min_id.min(id);
-        max_id = max_id.max(id);
-        segments_map.insert(id, seq.len());
-    }
-
-    // Create a dense vector for O(1) access
-    let num_segments = max_id - min_id + 1;
-    let mut segment_lengths = vec![0; num_segments];
-
-    for (id, len) in segments_map {
-        segment_lengths[id - min_id] = len;
-    }
+/// Output path that stands for standard output
+const STDOUT: &str = "-";
 
-    Ok((segment_lengths, min_id))
-}
-
-/// Project a GAF alignment file into coverage over GFA graph nodes
+/// Project GAF alignment files into coverage over GFA graph nodes
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Input GFA pangenome graph file (supports .gz/.bgz compression)
+    /// Input GFA pangenome graph file (gzip, bgzip, zstd, xz or bzip2
+    /// compression is detected from the content); not standard input, as
+    /// it may be read more than once
     #[arg(long)]
     gfa: String,
-    /// Input GAF alignment file
-    #[arg(short, long)]
-    gaf: String,
+    /// Input GAF alignment file(s), one sample each, optionally compressed
+    /// like the GFA; `-` reads standard input
+    #[arg(short, long, num_args = 1.., required_unless_present = "samples")]
+    gaf: Vec<String>,
+    /// Tab-separated sample sheet of `sample<TAB>gaf_path` lines, used
+    /// instead of --gaf
+    #[arg(long, conflicts_with = "gaf")]
+    samples: Option<String>,
+    /// Sample name(s) for the --gaf inputs, in the same order [default: GAF
+    /// file name without directories and .gaf[.gz] extensions]
+    #[arg(short = 'n', long, num_args = 1.., conflicts_with = "samples")]
+    sample_name: Vec<String>,
     /// Scale coverage values by node length
     #[arg(short, long)]
     len_scale: bool,
     /// Emit graph coverage vector in a single column
     #[arg(short, long)]
     coverage_column: bool,
+    /// Emit only nodes with non-zero coverage, as `node<TAB>coverage` rows
+    /// (`sample<TAB>node<TAB>coverage` triplets for several samples)
+    #[arg(short, long, conflicts_with = "coverage_column")]
+    sparse: bool,
     /// Weight coverage by query group occurrences
     #[arg(short = 'w', long)]
     weight_queries: bool,
+    /// How -w finds query groups: stream runs of consecutive records
+    /// (grouped), count keys in a first pass (two-pass), or pick grouped
+    /// when the input looks grouped (auto)
+    #[arg(long, value_enum, default_value_t = WeightMode::Auto)]
+    weight_mode: WeightMode,
+    /// Only count primary alignments, dropping those tagged tp:A:S
+    #[arg(long, conflicts_with = "secondary_weight")]
+    primary_only: bool,
+    /// Coverage weight of secondary alignments (tp:A:S) relative to primaries
+    #[arg(long, default_value_t = 1.0)]
+    secondary_weight: f64,
+    /// Emit one column per ID between the smallest and largest numeric node
+    /// ID, including IDs missing from the GFA (always the case with -c)
+    #[arg(long)]
+    dense_ids: bool,
+    /// Skip alignments with mapping quality below this value
+    #[arg(long, default_value_t = 0)]
+    min_mapq: u32,
+    /// Skip alignments with identity below this value, from the id:f tag
+    /// or matches / block length
+    #[arg(long, default_value_t = 0.0)]
+    min_identity: f64,
+    /// Skip alignments with an alignment block shorter than this
+    #[arg(long, default_value_t = 0)]
+    min_aligned_length: usize,
+    /// Credit only matched/mismatched bases, walking the cg:Z (or cs:Z) tag
+    /// along the path when present
+    #[arg(long)]
+    cigar: bool,
+    /// With --cigar, also report bases deleted from each node
+    #[arg(long, requires = "cigar")]
+    count_deletions: bool,
+    /// Also write per-base depth within each node to this file, as
+    /// `sample<TAB>node<TAB>start<TAB>end<TAB>depth` runs of constant
+    /// non-zero depth (0-based, half-open node offsets); `-` writes it to
+    /// standard output instead of node coverage
+    #[arg(long, value_name = "FILE")]
+    base_coverage: Option<String>,
+    /// Also write the weighted number of alignments traversing each GFA
+    /// link (L line) to this file; `-` writes them to standard output
+    /// instead of node coverage
+    #[arg(long, value_name = "FILE")]
+    edge_coverage: Option<String>,
+    /// What each alignment adds to a node: aligned bases, 1 if it covers
+    /// any base (reads), or the fraction of the node it covers; --bedgraph,
+    /// --windows and --path-summary need bases
+    #[arg(long, value_enum, default_value_t = CountMode::Bases)]
+    count_mode: CountMode,
+    /// Do not credit a node spanned by fewer target bases than this (an
+    /// integer) or by less than this fraction of its length (a decimal
+    /// between 0 and 1)
+    #[arg(long, default_value = "0")]
+    min_node_overlap: MinOverlap,
+    /// Also report coverage from alignments running along each node forward
+    /// and in reverse (the path step orientation, flipped for `-` strand
+    /// alignments), as extra rows or columns
+    #[arg(long)]
+    stranded: bool,
+    /// Write node depth (coverage divided by node length, whatever -l says)
+    /// projected onto the --ref-path paths to this file as bedGraph, one
+    /// track per sample; `-` writes it to standard output instead of node
+    /// coverage
+    #[arg(long, value_name = "FILE", requires = "ref_path")]
+    bedgraph: Option<String>,
+    /// Write coverage summarised over fixed-size windows along the
+    /// --ref-path paths to this file, as mean depth and covered fraction
+    /// per window; `-` writes it to standard output instead of node
+    /// coverage
+    #[arg(long, value_name = "FILE", requires = "ref_path")]
+    windows: Option<String>,
+    /// Window length in bases for --windows
+    #[arg(long, default_value_t = 1000, value_parser = clap::value_parser!(u64).range(1..))]
+    window_size: u64,
+    /// Write, for every GFA path and walk, its length-weighted mean depth
+    /// and the fraction of its nodes covered to this file; `-` writes it to
+    /// standard output instead of node coverage
+    #[arg(long, value_name = "FILE")]
+    path_summary: Option<String>,
+    /// Name of a GFA path (P line) or walk (W line, named
+    /// `sample#haplotype#sequence`) to project coverage onto
+    #[arg(long, num_args = 1..)]
+    ref_path: Vec<String>,
+    /// What to do with malformed records or records that do not fit the graph
+    #[arg(long, value_enum, default_value_t = ErrorPolicy::Fail)]
+    on_error: ErrorPolicy,
+    /// Number of threads parsing GAF records and accumulating coverage, and
+    /// decompressing BGZF inputs
+    #[arg(short, long, default_value_t = 1)]
+    threads: usize,
+    /// Instead of computing coverage, check each GAF record against the
+    /// graph and report unknown nodes, path length mismatches, coordinates
+    /// that do not fit the path and, with --cigar, cg/cs tags malformed or
+    /// not matching the target span; exits with status 1 if any are found
+    #[arg(long)]
+    validate: bool,
+}
+
+/// Read a sample sheet of `sample<TAB>gaf_path` lines, skipping blank and
+/// `#` comment lines
+fn read_sample_sheet(path: &str) -> Result<Vec<(String, String)>, Error> {
+    let reader = BufReader::new(File::open(path).map_err(|e| Error::io(path, e))?);
+    let mut samples = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| Error::io(path, e))?;
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((name, gaf)) = line.split_once('\t') else {
+            return Err(Error::record(
+                path,
+                i + 1,
+                RecordError::new("line", line, "expected `sample<TAB>gaf_path`"),
+            ));
+        };
+        samples.push((name.to_string(), gaf.to_string()));
+    }
+    Ok(samples)
 }
 
 fn main() {
     let args = Args::parse();
+    match run(&args) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("[gafpack] error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
 
-    // Parse GFA file
-    let (segment_lengths, min_id) = parse_gfa(&args.gfa).unwrap();
-    let num_segments = segment_lengths.len();
-
-    let mut coverage: Vec<f64> = vec![0.0; num_segments];
-
-    if args.weight_queries {
-        // First pass: count query occurrences
-        let mut query_counts: HashMap<String, usize> = HashMap::new();
-        for_each_line_in_file(&args.gaf, |l: &str| {
-            let fields: Vec<&str> = l.split('\t').collect();
-            if fields.len() >= 4 {
-                let query_key = format!("{}:{}:{}", fields[0], fields[2], fields[3]);
-                *query_counts.entry(query_key).or_insert(0) += 1;
+/// Run the requested mode; returns false if validation found problems
+fn run(args: &Args) -> Result<bool, Error> {
+    if args.gfa == STDIN {
+        Args::command()
+            .error(
+                ErrorKind::InvalidValue,
+                "--gfa cannot be `-`: the graph may be read more than once, so it must be a file",
+            )
+            .exit();
+    }
+    let samples = match &args.samples {
+        Some(sheet) => read_sample_sheet(sheet)?,
+        None if args.sample_name.is_empty() => args
+            .gaf
+            .iter()
+            .map(|g| (default_sample_name(g), g.clone()))
+            .collect(),
+        None => {
+            if args.sample_name.len() != args.gaf.len() {
+                Args::command()
+                    .error(
+                        ErrorKind::WrongNumberOfValues,
+                        format!(
+                            "got {} --sample-name values for {} --gaf inputs",
+                            args.sample_name.len(),
+                            args.gaf.len()
+                        ),
+                    )
+                    .exit();
             }
-        });
+            args.sample_name
+                .iter()
+                .cloned()
+                .zip(args.gaf.iter().cloned())
+                .collect()
+        }
+    };
 
-        // Second pass: calculate coverage with query count adjustment
-        for_each_line_in_file(&args.gaf, |l: &str| {
-            let fields: Vec<&str> = l.split('\t').collect();
-            let query_key = format!("{}:{}:{}", fields[0], fields[2], fields[3]);
-            let count = query_counts.get(&query_key).unwrap_or(&1);
+    // Path projections report depth, which reads and fractions are not
+    let projections = [&args.bedgraph, &args.windows, &args.path_summary];
+    if args.count_mode != CountMode::Bases && projections.iter().any(|p| p.is_some()) {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--bedgraph, --windows and --path-summary report depth in bases, so they need \
+                 --count-mode bases",
+            )
+            .exit();
+    }
 
-            for_each_step(
-                l,
-                |node_id, len| {
-                    coverage[node_id - min_id] += len as f64 / *count as f64;
-                },
-                |node_id| segment_lengths[node_id - min_id],
-            );
-        });
+    // Parse GFA file once for all samples. Column rows carry no node IDs,
+    // so they keep one row per ID in range for rows to map onto IDs.
+    let layout = if args.dense_ids || args.coverage_column {
+        IdLayout::Dense
     } else {
-        // Single pass without weighting
-        for_each_line_in_file(&args.gaf, |l: &str| {
-            for_each_step(
-                l,
-                |node_id, len| {
-                    coverage[node_id - min_id] += len as f64;
-                },
-                |node_id| segment_lengths[node_id - min_id],
-            );
-        });
+        IdLayout::Compact
+    };
+    let mut graph = GraphLengths::from_gfa(&args.gfa, layout, args.threads)?;
+
+    if args.validate {
+        return validate(&graph, &samples, args.cigar);
+    }
+    if args.edge_coverage.is_some() {
+        graph.load_links(&args.gfa, args.threads)?;
     }
+    let ref_paths = read_ref_paths(args, &graph)?;
 
-    if args.coverage_column {
-        println!("##sample: {}", args.gaf);
-        println!("#coverage");
-        for (i, v) in coverage.into_iter().enumerate() {
-            println!(
-                "{}",
-                if args.len_scale {
-                    v / segment_lengths[i] as f64
-                } else {
-                    v
-                }
-            );
-        }
+    let config = PackConfig {
+        filter: RecordFilter {
+            min_mapq: args.min_mapq,
+            min_identity: args.min_identity,
+            min_aligned_length: args.min_aligned_length,
+            primary_only: args.primary_only,
+            secondary_weight: args.secondary_weight,
+        },
+        coverage: CoverageOptions {
+            use_cigar: args.cigar,
+            count_deletions: args.count_deletions,
+            count_mode: args.count_mode,
+            min_node_overlap: args.min_node_overlap,
+            stranded: args.stranded,
+            base_depth: args.base_coverage.is_some(),
+            edges: args.edge_coverage.is_some(),
+        },
+        weight_queries: args.weight_queries,
+        weight_mode: args.weight_mode,
+        on_error: args.on_error,
+        threads: args.threads,
+    };
+
+    // An extra output sent to standard output replaces node coverage
+    let extra_outputs = [
+        &args.base_coverage,
+        &args.edge_coverage,
+        &args.bedgraph,
+        &args.windows,
+        &args.path_summary,
+    ];
+    let to_stdout = extra_outputs
+        .iter()
+        .filter(|path| path.as_deref() == Some(STDOUT))
+        .count();
+    if to_stdout > 1 {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "only one of --base-coverage, --edge-coverage, --bedgraph, --windows and \
+                 --path-summary can be `-`",
+            )
+            .exit();
+    }
+    let node_out: Box<dyn Write> = if to_stdout > 0 {
+        Box::new(io::sink())
     } else {
-        print!("#sample");
-        for n in min_id..min_id + num_segments {
-            print!("\tnode.{}", n);
+        Box::new(io::stdout().lock())
+    };
+    let mut out = BufWriter::new(node_out);
+    let output_error = |e| Error::io("<stdout>", e);
+
+    // Tabular and sparse rows are written as each sample finishes; the
+    // column format needs every sample before the first row
+    let triplets = samples.len() > 1;
+    if args.sparse {
+        let names = samples.iter().map(|(n, _)| n.as_str()).collect::<Vec<_>>();
+        write_sparse_header(&mut out, &names, triplets, &track_labels(&config.coverage))
+            .map_err(output_error)?;
+    } else if !args.coverage_column {
+        write_tabular_header(&mut out, &graph).map_err(output_error)?;
+    }
+    let mut base_out = match &args.base_coverage {
+        Some(path) => {
+            let mut base_out = create_output(path)?;
+            write_base_depth_header(&mut base_out).map_err(|e| Error::io(path, e))?;
+            Some((path, base_out))
         }
-        println!();
-        print!("{}", args.gaf);
-        for (i, v) in coverage.into_iter().enumerate() {
-            print!(
-                "\t{}",
-                if args.len_scale {
-                    v / segment_lengths[i] as f64
-                } else {
-                    v
-                }
-            );
+        None => None,
+    };
+    let mut edge_out = match &args.edge_coverage {
+        Some(path) => {
+            let mut edge_out = create_output(path)?;
+            write_edge_header(&mut edge_out).map_err(|e| Error::io(path, e))?;
+            Some((path, edge_out))
         }
-        println!();
+        None => None,
+    };
+    let mut bedgraph_out = match &args.bedgraph {
+        Some(path) => Some((path, create_output(path)?)),
+        None => None,
+    };
+    let mut window_out = match &args.windows {
+        Some(path) => {
+            let mut window_out = create_output(path)?;
+            write_window_header(&mut window_out).map_err(|e| Error::io(path, e))?;
+            Some((path, window_out))
+        }
+        None => None,
+    };
+    // Paths are summarised in one pass over the GFA once every sample is done
+    let summary_out = match &args.path_summary {
+        Some(path) => {
+            let mut summary_out = create_output(path)?;
+            write_path_summary_header(&mut summary_out).map_err(|e| Error::io(path, e))?;
+            Some((path, summary_out))
+        }
+        None => None,
+    };
+    let mut summary_samples = Vec::new();
+    let mut columns = Vec::new();
+    for (name, gaf) in samples {
+        let (coverage, stats) = pack_gaf(&gaf, &graph, &config)?;
+        report_filtered(args, &name, &stats);
+        if let (Some((path, base_out)), Some(depth)) = (&mut base_out, coverage.base_depth()) {
+            write_base_depth(base_out, &graph, &name, depth)
+                .map_err(|e| Error::io(path.as_str(), e))?;
+        }
+        if let (Some((path, edge_out)), Some(edges)) = (&mut edge_out, coverage.edges()) {
+            write_edge_rows(edge_out, &graph, &name, edges)
+                .map_err(|e| Error::io(path.as_str(), e))?;
+            if coverage.unlinked() > 0 {
+                eprintln!(
+                    "[gafpack] {}: {} traversed step junctions match no L line",
+                    name,
+                    coverage.unlinked()
+                );
+            }
+        }
+        if let Some((path, window_out)) = &mut window_out {
+            let size = args.window_size as usize;
+            write_window_rows(window_out, &graph, &ref_paths, &name, &coverage, size)
+                .map_err(|e| Error::io(path.as_str(), e))?;
+        }
+        if args.path_summary.is_some() {
+            summary_samples.push((name.clone(), coverage.values().to_vec()));
+        }
+        if let Some((path, bedgraph_out)) = &mut bedgraph_out {
+            write_bedgraph(bedgraph_out, &graph, &ref_paths, &name, &coverage)
+                .map_err(|e| Error::io(path.as_str(), e))?;
+        }
+        let sample = SampleCoverage::new(name, &coverage, &graph, args.len_scale);
+        if args.sparse {
+            write_sparse_rows(&mut out, &graph, &sample, triplets).map_err(output_error)?;
+        } else if args.coverage_column {
+            columns.push(sample);
+        } else {
+            write_tabular_row(&mut out, &sample).map_err(output_error)?;
+        }
+    }
+    if args.coverage_column {
+        write_columns(&mut out, &columns).map_err(output_error)?;
+    }
+    out.flush().map_err(output_error)?;
+    if let Some((path, mut base_out)) = base_out {
+        base_out.flush().map_err(|e| Error::io(path, e))?;
+    }
+    if let Some((path, mut edge_out)) = edge_out {
+        edge_out.flush().map_err(|e| Error::io(path, e))?;
+    }
+    if let Some((path, mut bedgraph_out)) = bedgraph_out {
+        bedgraph_out.flush().map_err(|e| Error::io(path, e))?;
+    }
+    if let Some((path, mut window_out)) = window_out {
+        window_out.flush().map_err(|e| Error::io(path, e))?;
+    }
+    if let Some((path, mut summary_out)) = summary_out {
+        let summary_error = |e| Error::io(path, e);
+        for_each_path(
+            &args.gfa,
+            &graph,
+            args.threads,
+            |_| true,
+            |gfa_path| {
+                write_path_summary_rows(&mut summary_out, &graph, &gfa_path, &summary_samples)
+                    .map_err(summary_error)
+            },
+        )?;
+        summary_out.flush().map_err(summary_error)?;
+    }
+    Ok(true)
+}
+
+/// Read the GFA paths named by --ref-path, in the order given
+fn read_ref_paths(args: &Args, graph: &GraphLengths) -> Result<Vec<GraphPath>, Error> {
+    if args.ref_path.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut found = Vec::new();
+    for_each_path(
+        &args.gfa,
+        graph,
+        args.threads,
+        |name| args.ref_path.iter().any(|r| r == name),
+        |path| {
+            found.push(path);
+            Ok(())
+        },
+    )?;
+    let mut paths = Vec::with_capacity(args.ref_path.len());
+    for name in &args.ref_path {
+        let Some(i) = found.iter().position(|p| &p.name == name) else {
+            Args::command()
+                .error(
+                    ErrorKind::InvalidValue,
+                    format!("no P or W line named `{}` in {}", name, args.gfa),
+                )
+                .exit();
+        };
+        paths.push(found.swap_remove(i));
+    }
+    Ok(paths)
+}
+
+/// Create an extra output file, or write to standard output for `-`
+fn create_output(path: &str) -> Result<BufWriter<Box<dyn Write>>, Error> {
+    let out: Box<dyn Write> = if path == STDOUT {
+        Box::new(io::stdout())
+    } else {
+        Box::new(File::create(path).map_err(|e| Error::io(path, e))?)
+    };
+    Ok(BufWriter::new(out))
+}
+
+/// Report every problem of every GAF against the graph as TSV on stdout,
+/// with per-file totals on stderr; returns false if any problem was found
+fn validate(
+    graph: &GraphLengths,
+    samples: &[(String, String)],
+    use_cigar: bool,
+) -> Result<bool, Error> {
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let output_error = |e| Error::io("<stdout>", e);
+    let mut valid = true;
+    writeln!(out, "#file\tline\tquery\tfield\tvalue\tproblem").map_err(output_error)?;
+    for (_, gaf) in samples {
+        let summary = validate_gaf(gaf, graph, use_cigar, |line, query, problem| {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                gaf, line, query, problem.field, problem.value, problem.message
+            )
+            .map_err(output_error)
+        })?;
+        eprintln!(
+            "[gafpack] {}: {} of {} records invalid ({} problems)",
+            gaf, summary.invalid_records, summary.records, summary.problems
+        );
+        valid &= summary.invalid_records == 0;
+    }
+    out.flush().map_err(output_error)?;
+    Ok(valid)
+}
+
+/// Report the number of records dropped by each active filter on stderr
+fn report_filtered(args: &Args, sample: &str, stats: &FilterStats) {
+    if args.primary_only {
+        eprintln!(
+            "[gafpack] {}: filtered {} secondary alignments",
+            sample, stats.secondary
+        );
+    }
+    if args.min_mapq > 0 {
+        eprintln!(
+            "[gafpack] {}: filtered {} records with MAPQ < {}",
+            sample, stats.mapq, args.min_mapq
+        );
+    }
+    if args.min_aligned_length > 0 {
+        eprintln!(
+            "[gafpack] {}: filtered {} records with aligned length < {}",
+            sample, stats.aligned_length, args.min_aligned_length
+        );
+    }
+    if stats.invalid > 0 {
+        eprintln!(
+            "[gafpack] {}: skipped {} invalid records",
+            sample, stats.invalid
+        );
+    }
+    if args.min_identity > 0.0 {
+        eprintln!(
+            "[gafpack] {}: filtered {} records with identity < {}",
+            sample, stats.identity, args.min_identity
+        );
     }
 }
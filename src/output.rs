@@ -0,0 +1,323 @@
+use crate::coverage::{Coverage, CoverageOptions};
+use crate::depth::BaseDepth;
+use crate::graph::GraphLengths;
+use crate::paths::{node_depth, GraphPath};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Derive a sample name from a GAF path by dropping its directories and any
+/// `.gaf` and compression extensions, e.g. `/data/HG002.gaf.gz` -> `HG002`
+pub fn default_sample_name(gaf_path: &str) -> String {
+    let mut name = Path::new(gaf_path)
+        .file_name()
+        .map_or(gaf_path, |n| n.to_str().unwrap_or(gaf_path));
+    for ext in [".gz", ".bgz", ".zst", ".xz", ".bz2"] {
+        if let Some(stem) = name.strip_suffix(ext) {
+            name = stem;
+            break;
+        }
+    }
+    name = name.strip_suffix(".gaf").unwrap_or(name);
+    if name.is_empty() {
+        gaf_path.to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Labels of the per-node tracks written after the coverage of each
+/// sample under the given options, in output order
+pub fn track_labels(options: &CoverageOptions) -> Vec<&'static str> {
+    let mut labels = Vec::new();
+    if options.use_cigar && options.count_deletions {
+        labels.push("deletions");
+    }
+    if options.stranded {
+        labels.extend(["forward", "reverse"]);
+    }
+    labels
+}
+
+/// Coverage of one sample, ready to be written
+#[derive(Debug, Clone)]
+pub struct SampleCoverage {
+    pub name: String,
+    pub values: Vec<f64>,
+    /// Additional labelled per-node vectors, such as deleted bases, in the
+    /// order of [`track_labels`]
+    pub tracks: Vec<(&'static str, Vec<f64>)>,
+}
+
+impl SampleCoverage {
+    /// Collect the (optionally length-scaled) vectors of a coverage accumulator
+    pub fn new(name: String, coverage: &Coverage, graph: &GraphLengths, len_scale: bool) -> Self {
+        let mut tracks = Vec::new();
+        if let Some(deletions) = coverage.scaled_deletions(graph, len_scale) {
+            tracks.push(("deletions", deletions));
+        }
+        if let Some((forward, reverse)) = coverage.scaled_strands(graph, len_scale) {
+            tracks.push(("forward", forward));
+            tracks.push(("reverse", reverse));
+        }
+        SampleCoverage {
+            name,
+            values: coverage.scaled(graph, len_scale),
+            tracks,
+        }
+    }
+}
+
+/// Write the `#sample` header of the tabular format, one column per node
+pub fn write_tabular_header(out: &mut impl Write, graph: &GraphLengths) -> io::Result<()> {
+    write!(out, "#sample")?;
+    for n in graph.node_names() {
+        write!(out, "\tnode.{}", n)?;
+    }
+    writeln!(out)
+}
+
+/// Write a sample's row of the tabular format, followed by a
+/// `<sample>.<label>` row per track
+pub fn write_tabular_row(out: &mut impl Write, sample: &SampleCoverage) -> io::Result<()> {
+    write!(out, "{}", sample.name)?;
+    for v in &sample.values {
+        write!(out, "\t{}", v)?;
+    }
+    writeln!(out)?;
+    for (label, track) in &sample.tracks {
+        write!(out, "{}.{}", sample.name, label)?;
+        for t in track {
+            write!(out, "\t{}", t)?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Write the column format: one row per node, one column per sample
+///
+/// A single sample keeps the `#coverage` header; several samples are
+/// labelled by name.
+pub fn write_columns(out: &mut impl Write, samples: &[SampleCoverage]) -> io::Result<()> {
+    for sample in samples {
+        writeln!(out, "##sample: {}", sample.name)?;
+    }
+    let mut header = Vec::new();
+    for sample in samples {
+        if samples.len() == 1 {
+            header.push("coverage".to_string());
+            header.extend(sample.tracks.iter().map(|(label, _)| label.to_string()));
+        } else {
+            header.push(sample.name.clone());
+            header.extend(
+                sample
+                    .tracks
+                    .iter()
+                    .map(|(label, _)| format!("{}.{}", sample.name, label)),
+            );
+        }
+    }
+    writeln!(out, "#{}", header.join("\t"))?;
+
+    let len = samples.first().map_or(0, |s| s.values.len());
+    for i in 0..len {
+        for (j, sample) in samples.iter().enumerate() {
+            if j > 0 {
+                write!(out, "\t")?;
+            }
+            write!(out, "{}", sample.values[i])?;
+            for (_, track) in &sample.tracks {
+                write!(out, "\t{}", track[i])?;
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Write the header of the sparse format
+///
+/// A single sample gets a `##sample:` line and `node<TAB>coverage` rows;
+/// with `triplets`, rows carry the sample name as a first column.
+pub fn write_sparse_header(
+    out: &mut impl Write,
+    samples: &[&str],
+    triplets: bool,
+    tracks: &[&str],
+) -> io::Result<()> {
+    if triplets {
+        write!(out, "#sample\tnode\tcoverage")?;
+    } else {
+        for sample in samples {
+            writeln!(out, "##sample: {}", sample)?;
+        }
+        write!(out, "#node\tcoverage")?;
+    }
+    for label in tracks {
+        write!(out, "\t{}", label)?;
+    }
+    writeln!(out)
+}
+
+/// Write a sample's nodes with non-zero coverage (or a non-zero track) in
+/// the sparse format
+pub fn write_sparse_rows(
+    out: &mut impl Write,
+    graph: &GraphLengths,
+    sample: &SampleCoverage,
+    triplets: bool,
+) -> io::Result<()> {
+    for (i, v) in sample.values.iter().enumerate() {
+        if *v == 0.0 && sample.tracks.iter().all(|(_, track)| track[i] == 0.0) {
+            continue;
+        }
+        if triplets {
+            write!(out, "{}\t", sample.name)?;
+        }
+        write!(out, "{}\t{}", graph.node_name(i), v)?;
+        for (_, track) in &sample.tracks {
+            write!(out, "\t{}", track[i])?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Write the header of the per-base depth output
+pub fn write_base_depth_header(out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "#sample\tnode\tstart\tend\tdepth")
+}
+
+/// Write a sample's per-base depth as runs of constant non-zero depth,
+/// with 0-based half-open coordinates within each node
+pub fn write_base_depth(
+    out: &mut impl Write,
+    graph: &GraphLengths,
+    sample: &str,
+    depth: &BaseDepth,
+) -> io::Result<()> {
+    for i in 0..graph.len() {
+        let runs = depth.runs(i);
+        if runs.is_empty() {
+            continue;
+        }
+        let name = graph.node_name(i);
+        for (start, end, d) in runs {
+            writeln!(out, "{}\t{}\t{}\t{}\t{}", sample, name, start, end, d)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the header of the edge coverage output
+pub fn write_edge_header(out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "#sample\tfrom\tfrom_orient\tto\tto_orient\tcoverage")
+}
+
+/// Write a sample's traversal count of every link, in L-line order
+pub fn write_edge_rows(
+    out: &mut impl Write,
+    graph: &GraphLengths,
+    sample: &str,
+    edges: &[f64],
+) -> io::Result<()> {
+    let orient = |reverse| if reverse { '-' } else { '+' };
+    for (link, coverage) in graph.links().iter().zip(edges) {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            sample,
+            graph.node_name(link.from),
+            orient(link.from_reverse),
+            graph.node_name(link.to),
+            orient(link.to_reverse),
+            coverage
+        )?;
+    }
+    Ok(())
+}
+
+/// Write a sample's node depth (raw coverage divided by node length)
+/// projected onto paths as a bedGraph track, merging adjacent steps with
+/// equal depth and omitting zeros
+pub fn write_bedgraph(
+    out: &mut impl Write,
+    graph: &GraphLengths,
+    paths: &[GraphPath],
+    sample: &str,
+    coverage: &Coverage,
+) -> io::Result<()> {
+    writeln!(out, "track type=bedGraph name=\"{}\"", sample)?;
+    for path in paths {
+        let mut intervals: Vec<(usize, usize, f64)> = Vec::new();
+        path.for_each_interval(graph, |start, end, index| {
+            let depth = node_depth(graph, coverage.values(), index);
+            match intervals.last_mut() {
+                Some(last) if last.1 == start && last.2 == depth => last.1 = end,
+                _ => intervals.push((start, end, depth)),
+            }
+        });
+        for (start, end, depth) in intervals {
+            if depth != 0.0 && start < end {
+                writeln!(out, "{}\t{}\t{}\t{}", path.name, start, end, depth)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write the header of the windowed path coverage output
+pub fn write_window_header(out: &mut impl Write) -> io::Result<()> {
+    writeln!(
+        out,
+        "#sample\tpath\tstart\tend\tmean_depth\tcovered_fraction"
+    )
+}
+
+/// Write a sample's coverage summarised over windows of `size` bases along
+/// each path
+pub fn write_window_rows(
+    out: &mut impl Write,
+    graph: &GraphLengths,
+    paths: &[GraphPath],
+    sample: &str,
+    coverage: &Coverage,
+    size: usize,
+) -> io::Result<()> {
+    for path in paths {
+        for w in path.windows(graph, coverage.values(), size) {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                sample, path.name, w.start, w.end, w.mean_depth, w.covered_fraction
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the header of the per-path summary output
+pub fn write_path_summary_header(out: &mut impl Write) -> io::Result<()> {
+    writeln!(
+        out,
+        "#sample\tpath\tlength\tnodes\tmean_depth\tcovered_fraction"
+    )
+}
+
+/// Write the coverage summary of a path for each sample's raw coverage
+pub fn write_path_summary_rows(
+    out: &mut impl Write,
+    graph: &GraphLengths,
+    path: &GraphPath,
+    samples: &[(String, Vec<f64>)],
+) -> io::Result<()> {
+    for (sample, values) in samples {
+        let s = path.summary(graph, values);
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            sample, path.name, s.length, s.nodes, s.mean_depth, s.covered_fraction
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,322 @@
+use crate::coverage::{Coverage, CoverageOptions};
+use crate::error::{Error, ErrorPolicy, RecordError};
+use crate::filter::{FilterStats, RecordFilter};
+use crate::gaf::GafRecord;
+use crate::graph::GraphLengths;
+use crate::io::{open_input, STDIN};
+use crate::parallel::{process_lines, GroupCheck};
+use std::collections::HashMap;
+use std::io::prelude::*;
+
+/// Number of leading records inspected to decide whether input is grouped
+pub const GROUPING_PROBE_RECORDS: usize = 100_000;
+
+/// How query groups are found when weighting coverage by query
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum WeightMode {
+    /// Stream grouped input (always the case for standard input); otherwise
+    /// read the file twice
+    #[default]
+    Auto,
+    /// Treat each run of consecutive records with the same query key as a
+    /// group, buffering one group at a time
+    Grouped,
+    /// Count every query key in a first pass, then accumulate in a second
+    TwoPass,
+}
+
+/// Settings for projecting a GAF file onto graph coverage
+#[derive(Debug, Clone, Default)]
+pub struct PackConfig {
+    pub filter: RecordFilter,
+    pub coverage: CoverageOptions,
+    /// Weight coverage by query group occurrences
+    pub weight_queries: bool,
+    /// How query groups are found when `weight_queries` is set
+    pub weight_mode: WeightMode,
+    /// What to do with malformed records
+    pub on_error: ErrorPolicy,
+    /// Worker threads parsing and accumulating records; 0 or 1 processes
+    /// the file in the calling thread
+    pub threads: usize,
+}
+
+/// Compute the coverage of one GAF file over the graph
+///
+/// `gaf_path` may be `-` to read standard input, which is always weighted
+/// in grouped mode as it can only be read once. In `Auto` mode a file is
+/// streamed in grouped mode if no query key recurs after its run ends
+/// within the first [`GROUPING_PROBE_RECORDS`] records, and read twice
+/// otherwise. Grouped streaming keeps checking the rest of the input in
+/// bounded memory: a query recurring within the last
+/// [`GROUP_CHECK_WINDOW`](crate::parallel::GROUP_CHECK_WINDOW) queries
+/// restarts an `Auto` file with two passes, and fails with
+/// [`Error::Ungrouped`] for standard input or `Grouped` mode. Recurrences
+/// further apart are not detected, so grouped input must keep every
+/// query's records together.
+///
+/// With several threads, per-thread coverage is summed at the end, so
+/// values can differ from a single-threaded run in the last digits.
+///
+/// Returns the accumulated coverage and the counts of records dropped by
+/// the filter or, under the `skip`/`warn` policies, for being invalid.
+pub fn pack_gaf(
+    gaf_path: &str,
+    graph: &GraphLengths,
+    config: &PackConfig,
+) -> Result<(Coverage, FilterStats), Error> {
+    let threads = config.threads;
+    // Each pass over the file gets fresh packers, whose coverage shares one
+    // per-base depth array rather than a copy per worker
+    let new_packers = || {
+        let shared = Coverage::with_options(graph, config.coverage.clone());
+        move || Packer::new(gaf_path, graph, config, shared.fork(graph))
+    };
+    let no_finish = |_: &mut Packer| Ok(());
+
+    let grouped = config.weight_queries
+        && match config.weight_mode {
+            _ if gaf_path == STDIN => true,
+            WeightMode::Grouped => true,
+            WeightMode::TwoPass => false,
+            WeightMode::Auto => is_grouped(gaf_path, GROUPING_PROBE_RECORDS, threads)?,
+        };
+
+    let two_pass = || -> Result<Vec<Packer>, Error> {
+        // First pass: sum the weights of the records of each query group
+        // that pass the filter. Invalid records are reported in the second
+        // pass.
+        let query_counts = process_lines(
+            gaf_path,
+            threads,
+            false,
+            HashMap::new,
+            |counts, line_no, l| count_query(counts, gaf_path, config, line_no, l),
+            |_| Ok(()),
+        )?
+        .into_iter()
+        .reduce(|mut all, counts| {
+            for (key, weight) in counts {
+                *all.entry(key).or_insert(0.0) += weight;
+            }
+            all
+        })
+        .unwrap_or_default();
+
+        // Second pass: calculate coverage with query count adjustment
+        process_lines(
+            gaf_path,
+            threads,
+            false,
+            new_packers(),
+            |p, line_no, l| p.weighted_line(line_no, l, &query_counts),
+            no_finish,
+        )
+    };
+
+    let packers = if !config.weight_queries {
+        process_lines(
+            gaf_path,
+            threads,
+            false,
+            new_packers(),
+            |p, line_no, l| p.unweighted_line(line_no, l),
+            no_finish,
+        )?
+    } else if grouped {
+        let result = process_lines(
+            gaf_path,
+            threads,
+            true,
+            new_packers(),
+            |p, line_no, l| p.grouped_line(line_no, l),
+            |p| p.flush_group(),
+        );
+        match result {
+            // The probe only saw the start of the file: restart with two
+            // passes rather than splitting a query group
+            Err(Error::Ungrouped { line, query, .. })
+                if config.weight_mode == WeightMode::Auto && gaf_path != STDIN =>
+            {
+                eprintln!(
+                    "[gafpack] {}: query `{}` recurs at line {}; reading the file twice",
+                    gaf_path, query, line
+                );
+                two_pass()?
+            }
+            result => result?,
+        }
+    } else {
+        two_pass()?
+    };
+
+    let mut packers = packers.into_iter();
+    let mut packer = packers.next().unwrap_or_else(new_packers());
+    for other in packers {
+        packer.coverage.merge(&other.coverage);
+        packer.stats.merge(&other.stats);
+    }
+    Ok((packer.coverage, packer.stats))
+}
+
+/// Add the weight of a line's record to its query group, for the first
+/// pass of two-pass weighting
+fn count_query(
+    counts: &mut HashMap<String, f64>,
+    gaf_path: &str,
+    config: &PackConfig,
+    line_no: usize,
+    l: &str,
+) -> Result<(), Error> {
+    let record = match GafRecord::parse(l) {
+        Ok(record) => record,
+        Err(e) if config.on_error == ErrorPolicy::Fail => {
+            return Err(Error::record(gaf_path, line_no, e))
+        }
+        Err(_) => return Ok(()),
+    };
+    if config.filter.rejects(&record).is_none() {
+        *counts.entry(record.query_key()).or_insert(0.0) += config.filter.weight(&record);
+    }
+    Ok(())
+}
+
+/// Whether no query recurs after its run of consecutive records ends,
+/// looking at up to `limit` leading lines; lines without query columns are
+/// ignored
+pub fn is_grouped(gaf_path: &str, limit: usize, threads: usize) -> Result<bool, Error> {
+    let reader = open_input(gaf_path, threads)?;
+    let mut groups = GroupCheck::default();
+    for (i, line) in reader.lines().take(limit).enumerate() {
+        let line = line.map_err(|e| Error::io(gaf_path, e))?;
+        if groups.check(gaf_path, i + 1, &line).is_err() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Coverage accumulation state for one GAF file
+struct Packer<'a> {
+    gaf_path: &'a str,
+    graph: &'a GraphLengths,
+    config: &'a PackConfig,
+    coverage: Coverage,
+    stats: FilterStats,
+    /// Query key and buffered (line_number, line, weight) of the current
+    /// group in grouped mode
+    group_key: String,
+    group: Vec<(usize, String, f64)>,
+}
+
+impl<'a> Packer<'a> {
+    fn new(
+        gaf_path: &'a str,
+        graph: &'a GraphLengths,
+        config: &'a PackConfig,
+        coverage: Coverage,
+    ) -> Self {
+        Packer {
+            gaf_path,
+            graph,
+            config,
+            coverage,
+            stats: FilterStats::default(),
+            group_key: String::new(),
+            group: Vec::new(),
+        }
+    }
+
+    /// Apply the error policy to a failed record; returns Ok if it was skipped
+    fn handle(&mut self, line_no: usize, error: RecordError) -> Result<(), Error> {
+        let error = Error::record(self.gaf_path, line_no, error);
+        match self.config.on_error {
+            ErrorPolicy::Fail => return Err(error),
+            ErrorPolicy::Warn => eprintln!("[gafpack] warning: skipping {}", error),
+            ErrorPolicy::Skip => {}
+        }
+        self.stats.invalid += 1;
+        Ok(())
+    }
+
+    /// Parse and filter a line, counting dropped records; returns the
+    /// record if it contributes coverage
+    fn accept<'l>(&mut self, line_no: usize, l: &'l str) -> Result<Option<GafRecord<'l>>, Error> {
+        let record = match GafRecord::parse(l) {
+            Ok(record) => record,
+            Err(e) => return self.handle(line_no, e).map(|_| None),
+        };
+        if let Some(reason) = self.config.filter.rejects(&record) {
+            self.stats.add(reason);
+            return Ok(None);
+        }
+        Ok(Some(record))
+    }
+
+    fn add(&mut self, line_no: usize, record: &GafRecord, weight: f64) -> Result<(), Error> {
+        match self.coverage.add_record(self.graph, record, weight) {
+            Ok(()) => Ok(()),
+            Err(e) => self.handle(line_no, e),
+        }
+    }
+
+    /// Add a line without query weighting
+    fn unweighted_line(&mut self, line_no: usize, l: &str) -> Result<(), Error> {
+        if let Some(record) = self.accept(line_no, l)? {
+            let weight = self.config.filter.weight(&record);
+            self.add(line_no, &record, weight)?;
+        }
+        Ok(())
+    }
+
+    /// Buffer a line into the current run of records sharing a query key,
+    /// flushing the previous run when the key changes
+    fn grouped_line(&mut self, line_no: usize, l: &str) -> Result<(), Error> {
+        let Some(record) = self.accept(line_no, l)? else {
+            return Ok(());
+        };
+        let key = record.query_key();
+        let weight = self.config.filter.weight(&record);
+        if key != self.group_key {
+            self.flush_group()?;
+            self.group_key = key;
+        }
+        self.group.push((line_no, l.to_string(), weight));
+        Ok(())
+    }
+
+    /// Add the buffered group, each record weighted by its share of the
+    /// group's total weight
+    fn flush_group(&mut self) -> Result<(), Error> {
+        let group = std::mem::take(&mut self.group);
+        let total: f64 = group.iter().map(|(_, _, w)| w).sum();
+        for (line_no, l, weight) in group {
+            // Buffered lines parsed successfully when first read
+            let record =
+                GafRecord::parse(&l).map_err(|e| Error::record(self.gaf_path, line_no, e))?;
+            if total > 0.0 {
+                self.add(line_no, &record, weight / total)?;
+            }
+        }
+        self.group_key.clear();
+        Ok(())
+    }
+
+    /// Add a line weighted by its share of its query group's total weight
+    fn weighted_line(
+        &mut self,
+        line_no: usize,
+        l: &str,
+        query_counts: &HashMap<String, f64>,
+    ) -> Result<(), Error> {
+        let Some(record) = self.accept(line_no, l)? else {
+            return Ok(());
+        };
+        let total = *query_counts.get(&record.query_key()).unwrap_or(&1.0);
+        let weight = self.config.filter.weight(&record);
+        if total > 0.0 {
+            self.add(line_no, &record, weight / total)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+use crate::error::Error;
+use crate::gaf::query_columns;
+use crate::io::{for_each_line_in_file, open_input};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::io::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+
+/// Number of lines handed to a worker at a time
+pub const CHUNK_LINES: usize = 16_384;
+
+/// Lines of a file with the 1-based number of the first one
+struct Chunk {
+    first_line: usize,
+    lines: Vec<String>,
+}
+
+/// Number of most recent query groups checked for recurrence when
+/// `keep_groups` is set in [`process_lines`]
+pub const GROUP_CHECK_WINDOW: usize = 1 << 20;
+
+/// Checks that the records of each query form a single run of lines
+///
+/// Only 64-bit hashes of the last [`GROUP_CHECK_WINDOW`] queries are kept,
+/// so memory stays bounded however many queries the input holds; a query
+/// recurring after that many others is not detected.
+#[derive(Default)]
+pub(crate) struct GroupCheck {
+    current: Option<u64>,
+    recent: VecDeque<u64>,
+    closed: HashSet<u64>,
+}
+
+impl GroupCheck {
+    /// Record the query of a line, failing if its run of lines had ended;
+    /// returns whether the line starts a new run. Lines without query
+    /// columns are ignored, so they never start a run.
+    pub(crate) fn check(
+        &mut self,
+        filename: &str,
+        line_no: usize,
+        line: &str,
+    ) -> Result<bool, Error> {
+        let Some(key) = query_columns(line) else {
+            return Ok(false);
+        };
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+        if self.current == Some(hash) {
+            return Ok(false);
+        }
+        if self.closed.contains(&hash) {
+            return Err(Error::Ungrouped {
+                path: filename.to_string(),
+                line: line_no,
+                query: key.0.to_string(),
+            });
+        }
+        if let Some(previous) = self.current.replace(hash) {
+            if self.recent.len() == GROUP_CHECK_WINDOW {
+                if let Some(oldest) = self.recent.pop_front() {
+                    self.closed.remove(&oldest);
+                }
+            }
+            self.recent.push_back(previous);
+            self.closed.insert(previous);
+        }
+        Ok(true)
+    }
+}
+
+/// Process the lines of a file on `threads` workers, each owning a state
+///
+/// The reader hands out chunks of consecutive lines; every worker folds its
+/// chunks into its own state with `process`, calling `finish` at the end of
+/// each chunk. With `keep_groups`, chunks are only cut where the query
+/// group (see [`query_columns`]) changes, so a run of records of one query
+/// always lands in a single chunk, and a query whose records recur after
+/// another query's fails with [`Error::Ungrouped`]. Only the last
+/// [`GROUP_CHECK_WINDOW`] queries are remembered, as 64-bit hashes, so a
+/// query recurring after more than that many others is not detected.
+///
+/// Returns one state per worker. With one thread (or zero) the file is
+/// processed in the calling thread and a single state is returned.
+pub fn process_lines<S: Send>(
+    filename: &str,
+    threads: usize,
+    keep_groups: bool,
+    init: impl Fn() -> S + Sync,
+    process: impl Fn(&mut S, usize, &str) -> Result<(), Error> + Sync,
+    finish: impl Fn(&mut S) -> Result<(), Error> + Sync,
+) -> Result<Vec<S>, Error> {
+    if threads <= 1 {
+        let mut state = init();
+        let mut groups = keep_groups.then(GroupCheck::default);
+        for_each_line_in_file(filename, |line_no, l| {
+            if let Some(groups) = &mut groups {
+                groups.check(filename, line_no, l)?;
+            }
+            process(&mut state, line_no, l)
+        })?;
+        finish(&mut state)?;
+        return Ok(vec![state]);
+    }
+
+    let failed = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::sync_channel::<Chunk>(threads * 2);
+    let receiver = Mutex::new(receiver);
+
+    thread::scope(|scope| {
+        let workers = (0..threads)
+            .map(|_| {
+                scope.spawn(|| -> Result<S, Error> {
+                    let mut state = init();
+                    let mut error = None;
+                    // Keep draining after a failure so the reader never
+                    // blocks on a full channel
+                    loop {
+                        // Hold the lock only while receiving
+                        let next = receiver.lock().unwrap().recv();
+                        let Ok(chunk) = next else {
+                            break;
+                        };
+                        if failed.load(Ordering::Relaxed) {
+                            continue;
+                        }
+                        let result = chunk
+                            .lines
+                            .iter()
+                            .enumerate()
+                            .try_for_each(|(i, l)| process(&mut state, chunk.first_line + i, l))
+                            .and_then(|_| finish(&mut state));
+                        if let Err(e) = result {
+                            failed.store(true, Ordering::Relaxed);
+                            error = Some(e);
+                        }
+                    }
+                    match error {
+                        Some(e) => Err(e),
+                        None => Ok(state),
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let read_result = read_chunks(filename, threads, keep_groups, &failed, |chunk| {
+            // Workers only hang up after a failure, which is reported below
+            let _ = sender.send(chunk);
+        });
+        drop(sender);
+
+        let mut states = Vec::with_capacity(threads);
+        let mut error = None;
+        for worker in workers {
+            match worker.join().unwrap() {
+                Ok(state) => states.push(state),
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        match (read_result, error) {
+            (Err(e), _) | (Ok(()), Some(e)) => Err(e),
+            (Ok(()), None) => Ok(states),
+        }
+    })
+}
+
+/// Read a file in chunks of about [`CHUNK_LINES`] lines until it ends or
+/// `failed` is set, decompressing BGZF input on `threads` threads; with
+/// `keep_groups`, a query recurring after its run of lines sets `failed`
+/// and stops the reading with an error
+fn read_chunks(
+    filename: &str,
+    threads: usize,
+    keep_groups: bool,
+    failed: &AtomicBool,
+    mut send: impl FnMut(Chunk),
+) -> Result<(), Error> {
+    let reader = open_input(filename, threads)?;
+    let mut chunk = Chunk {
+        first_line: 1,
+        lines: Vec::with_capacity(CHUNK_LINES),
+    };
+    let mut groups = keep_groups.then(GroupCheck::default);
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| Error::io(filename, e))?;
+        // Without groups to keep, a chunk can end before any line
+        let starts_group = match &mut groups {
+            Some(groups) => match groups.check(filename, i + 1, &line) {
+                Ok(starts_group) => starts_group,
+                Err(e) => {
+                    failed.store(true, Ordering::Relaxed);
+                    return Err(e);
+                }
+            },
+            None => true,
+        };
+        if chunk.lines.len() >= CHUNK_LINES && starts_group {
+            if failed.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            let full = std::mem::replace(
+                &mut chunk,
+                Chunk {
+                    first_line: i + 1,
+                    lines: Vec::with_capacity(CHUNK_LINES),
+                },
+            );
+            send(full);
+        }
+        chunk.lines.push(line);
+    }
+    if !chunk.lines.is_empty() {
+        send(chunk);
+    }
+    Ok(())
+}
@@ -0,0 +1,242 @@
+use crate::error::{Error, RecordError};
+use crate::gaf::split_walk;
+use crate::graph::GraphLengths;
+use crate::io::open_input;
+use std::io::prelude::*;
+
+/// A path (P line) or walk (W line) embedded in a GFA graph
+#[derive(Debug, Clone)]
+pub struct GraphPath {
+    /// Path name; walks are named `sample#haplotype#sequence`
+    pub name: String,
+    /// Coordinate of the path's first base on its sequence: the start of a
+    /// W line, 0 for P lines
+    pub offset: usize,
+    /// Dense node indices with their orientation, `true` for reverse
+    pub steps: Vec<(usize, bool)>,
+}
+
+impl GraphPath {
+    /// Total length of the path's nodes
+    pub fn len(&self, graph: &GraphLengths) -> usize {
+        self.steps.iter().map(|(i, _)| graph.node_len(*i)).sum()
+    }
+
+    /// Call `callback` with (start, end, node_index) for each step, where
+    /// `[start, end)` are the step's coordinates on the path's sequence
+    pub fn for_each_interval(
+        &self,
+        graph: &GraphLengths,
+        mut callback: impl FnMut(usize, usize, usize),
+    ) {
+        let mut pos = self.offset;
+        for &(index, _) in &self.steps {
+            let len = graph.node_len(index);
+            callback(pos, pos + len, index);
+            pos += len;
+        }
+    }
+
+    /// Summarise raw node coverage `values` over the whole path
+    pub fn summary(&self, graph: &GraphLengths, values: &[f64]) -> PathSummary {
+        // Weighting each step's depth by its length sums its node's coverage
+        let length = self.len(graph);
+        let total: f64 = self.steps.iter().map(|(i, _)| values[*i]).sum();
+        let mut nodes = self.steps.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+        nodes.sort_unstable();
+        nodes.dedup();
+        let covered = nodes.iter().filter(|i| values[**i] != 0.0).count();
+        PathSummary {
+            length,
+            nodes: nodes.len(),
+            mean_depth: if length > 0 {
+                total / length as f64
+            } else {
+                0.0
+            },
+            covered_fraction: if nodes.is_empty() {
+                0.0
+            } else {
+                covered as f64 / nodes.len() as f64
+            },
+        }
+    }
+
+    /// Summarise raw node coverage `values` over consecutive windows of
+    /// `size` bases along the path
+    pub fn windows(&self, graph: &GraphLengths, values: &[f64], size: usize) -> Vec<PathWindow> {
+        let size = size.max(1);
+        let end = self.offset + self.len(graph);
+        let mut windows = (self.offset..end)
+            .step_by(size)
+            .map(|start| PathWindow {
+                start,
+                end: (start + size).min(end),
+                mean_depth: 0.0,
+                covered_fraction: 0.0,
+            })
+            .collect::<Vec<_>>();
+
+        // Accumulate depth and covered bases, then divide by window length
+        self.for_each_interval(graph, |mut start, end, index| {
+            let depth = node_depth(graph, values, index);
+            while start < end {
+                let window = &mut windows[(start - self.offset) / size];
+                let bases = end.min(window.end) - start;
+                window.mean_depth += depth * bases as f64;
+                if values[index] != 0.0 {
+                    window.covered_fraction += bases as f64;
+                }
+                start += bases;
+            }
+        });
+        for window in &mut windows {
+            let len = (window.end - window.start) as f64;
+            window.mean_depth /= len;
+            window.covered_fraction /= len;
+        }
+        windows
+    }
+}
+
+/// Coverage summary of a fixed-size window along a path
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathWindow {
+    /// Window start on the path's sequence
+    pub start: usize,
+    /// Window end (exclusive); the last window stops at the path end
+    pub end: usize,
+    /// Mean depth over the window's bases, each base taking its node's
+    /// coverage divided by the node length
+    pub mean_depth: f64,
+    /// Fraction of the window's bases on nodes with non-zero coverage
+    pub covered_fraction: f64,
+}
+
+/// Coverage summary of a whole path
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathSummary {
+    /// Total length of the path's steps
+    pub length: usize,
+    /// Number of distinct nodes the path visits
+    pub nodes: usize,
+    /// Length-weighted mean depth of the visited nodes, each step taking
+    /// its node's coverage divided by the node length
+    pub mean_depth: f64,
+    /// Fraction of the distinct nodes with non-zero coverage
+    pub covered_fraction: f64,
+}
+
+/// Mean depth of a node: its raw coverage, counted in bases, divided by its
+/// length
+pub fn node_depth(graph: &GraphLengths, values: &[f64], index: usize) -> f64 {
+    values[index] / graph.node_len(index).max(1) as f64
+}
+
+/// Stream the paths and walks of a GFA file, in file order
+///
+/// # Arguments
+/// * `gfa_path` - GFA file, optionally compressed
+/// * `graph` - Graph used to resolve step names
+/// * `threads` - Threads decompressing BGZF input
+/// * `wanted` - Called with each path name; only paths it accepts are
+///   parsed and passed on
+/// * `callback` - Function called with each wanted path; an error stops
+///   the iteration
+pub fn for_each_path(
+    gfa_path: &str,
+    graph: &GraphLengths,
+    threads: usize,
+    mut wanted: impl FnMut(&str) -> bool,
+    mut callback: impl FnMut(GraphPath) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut reader = open_input(gfa_path, threads)?;
+    let mut line = String::new();
+    let mut line_no = 0;
+
+    loop {
+        line.clear();
+        let bytes_read = reader
+            .read_line(&mut line)
+            .map_err(|e| Error::io(gfa_path, e))?;
+        if bytes_read == 0 {
+            break;
+        }
+        line_no += 1;
+
+        let line_str = line.trim();
+        let fields = match line_str.as_bytes().first() {
+            Some(b'P' | b'W') => line_str.split('\t').collect::<Vec<_>>(),
+            _ => continue,
+        };
+        let path = match fields[0] {
+            // Parse path line format: P<tab>name<tab>step+,step-,...
+            "P" if fields.len() >= 3 => {
+                if !wanted(fields[1]) {
+                    continue;
+                }
+                let steps = fields[2]
+                    .split(',')
+                    .map(|step| {
+                        if let Some(name) = step.strip_suffix('+') {
+                            Ok((name, false))
+                        } else if let Some(name) = step.strip_suffix('-') {
+                            Ok((name, true))
+                        } else {
+                            Err(RecordError::new(
+                                "path",
+                                step,
+                                "step lacks a +/- orientation",
+                            ))
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| Error::record(gfa_path, line_no, e))?;
+                resolve(fields[1].to_string(), 0, &steps, graph)
+            }
+            // Parse walk line format:
+            // W<tab>sample<tab>haplotype<tab>sequence<tab>start<tab>end<tab>walk
+            "W" if fields.len() >= 7 => {
+                let name = format!("{}#{}#{}", fields[1], fields[2], fields[3]);
+                if !wanted(&name) {
+                    continue;
+                }
+                let offset = match fields[4] {
+                    "*" => Ok(0),
+                    start => start.parse::<usize>().map_err(|_| {
+                        RecordError::new("start", start, "not a non-negative integer")
+                    }),
+                }
+                .map_err(|e| Error::record(gfa_path, line_no, e))?;
+                resolve(name, offset, &split_walk(fields[6]), graph)
+            }
+            _ => continue,
+        }
+        .map_err(|e| Error::record(gfa_path, line_no, e))?;
+        callback(path)?;
+    }
+    Ok(())
+}
+
+/// Look up the nodes of named steps
+fn resolve(
+    name: String,
+    offset: usize,
+    steps: &[(&str, bool)],
+    graph: &GraphLengths,
+) -> Result<GraphPath, RecordError> {
+    let steps = steps
+        .iter()
+        .map(|&(step, reverse)| {
+            graph
+                .index_of(step)
+                .map(|index| (index, reverse))
+                .ok_or_else(|| RecordError::new("path", step, "node not in graph"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(GraphPath {
+        name,
+        offset,
+        steps,
+    })
+}
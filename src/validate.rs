@@ -0,0 +1,53 @@
+use crate::error::{Error, RecordError};
+use crate::gaf::GafRecord;
+use crate::graph::GraphLengths;
+use crate::io::for_each_line_in_file;
+
+/// Totals of a validation pass over a GAF file
+#[derive(Debug, Clone, Default)]
+pub struct ValidationSummary {
+    /// Records read
+    pub records: usize,
+    /// Records with at least one problem
+    pub invalid_records: usize,
+    /// Problems found across all records
+    pub problems: usize,
+}
+
+/// Check every record of a GAF file against the graph, including its
+/// `cg`/`cs` tag with `use_cigar`
+///
+/// Calls `report` with (line_number, query_name, problem) for each problem
+/// found; records that cannot be parsed are reported with an empty query
+/// name. Only I/O failures, including those returned by `report`, stop the
+/// pass.
+pub fn validate_gaf(
+    gaf_path: &str,
+    graph: &GraphLengths,
+    use_cigar: bool,
+    mut report: impl FnMut(usize, &str, &RecordError) -> Result<(), Error>,
+) -> Result<ValidationSummary, Error> {
+    let mut summary = ValidationSummary::default();
+    for_each_line_in_file(gaf_path, |line_no, l| {
+        summary.records += 1;
+        let problems = match GafRecord::parse(l) {
+            Ok(record) => {
+                let problems = record.check_against(graph, use_cigar);
+                for problem in &problems {
+                    report(line_no, record.query_name, problem)?;
+                }
+                problems.len()
+            }
+            Err(e) => {
+                report(line_no, "", &e)?;
+                1
+            }
+        };
+        if problems > 0 {
+            summary.invalid_records += 1;
+            summary.problems += problems;
+        }
+        Ok(())
+    })?;
+    Ok(summary)
+}
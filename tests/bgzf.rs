@@ -0,0 +1,113 @@
+mod common;
+
+use common::{bgzf, bgzf_block, gafpack, gfa, record, TempDir, BGZF_EOF, NODES};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use gafpack::bgzf::{is_bgzf, ParallelBgzfReader};
+use std::io::{self, prelude::*};
+
+fn sample_data() -> Vec<u8> {
+    (0..50_000)
+        .flat_map(|i| format!("read{}\t{}\t>{}>{}\n", i, i * 7 % 1000, i, i + 1).into_bytes())
+        .collect()
+}
+
+fn read_all(bytes: &[u8], threads: usize) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    ParallelBgzfReader::new(bytes, threads).read_to_end(&mut out)?;
+    Ok(out)
+}
+
+#[test]
+fn round_trip_matches_gzip_decoder() {
+    let data = sample_data();
+    let compressed = bgzf(&data, 60_000);
+    assert!(is_bgzf(&compressed));
+    assert!(compressed.ends_with(&BGZF_EOF));
+
+    // The blocks form a valid multi-member gzip file
+    let mut gunzipped = Vec::new();
+    MultiGzDecoder::new(&compressed[..])
+        .read_to_end(&mut gunzipped)
+        .unwrap();
+    assert_eq!(gunzipped, data);
+
+    // Batches of several blocks, split unevenly across threads
+    for threads in [1, 2, 7] {
+        assert_eq!(read_all(&compressed, threads).unwrap(), data);
+    }
+    let small_blocks = bgzf(&data, 1000);
+    assert_eq!(read_all(&small_blocks, 3).unwrap(), data);
+}
+
+#[test]
+fn empty_blocks() {
+    assert!(is_bgzf(&BGZF_EOF));
+    assert_eq!(read_all(&BGZF_EOF, 2).unwrap(), Vec::<u8>::new());
+    assert_eq!(read_all(&[], 2).unwrap(), Vec::<u8>::new());
+
+    // An empty block in the middle of the stream decompresses to nothing
+    let mut compressed = bgzf_block(b"first\n");
+    compressed.extend_from_slice(&BGZF_EOF);
+    compressed.extend_from_slice(&bgzf(b"second\n", 100));
+    assert_eq!(read_all(&compressed, 2).unwrap(), b"first\nsecond\n");
+}
+
+#[test]
+fn truncated_block() {
+    let compressed = bgzf(&sample_data(), 60_000);
+    let first = bgzf_block(&sample_data()[..60_000]);
+    let cut = &compressed[..first.len() + 100];
+    let error = read_all(cut, 2).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+
+    // Cut inside the fixed header
+    let error = read_all(&compressed[..5], 2).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn bad_crc() {
+    let mut compressed = bgzf(b"some alignments\n", 100);
+    let crc_at = compressed.len() - BGZF_EOF.len() - 8;
+    compressed[crc_at] ^= 0xff;
+    let error = read_all(&compressed, 2).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn plain_gzip_is_not_bgzf() {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&sample_data()).unwrap();
+    let gzip = encoder.finish().unwrap();
+    assert!(!is_bgzf(&gzip));
+    assert!(!is_bgzf(&BGZF_EOF[..10]));
+
+    let error = read_all(&gzip, 2).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn bgzf_gaf_on_stdin() {
+    let dir = TempDir::new("bgzf-stdin");
+    let gfa = dir.write("graph.gfa", gfa());
+    let gaf = (0..20_000)
+        .map(|i| record(&format!("read{}", i), i % (NODES - 1) + 1))
+        .collect::<String>();
+    let plain = gafpack(&["--gfa", &gfa, "--gaf", "-", "-n", "s"], gaf.as_bytes());
+    let compressed = bgzf(gaf.as_bytes(), 30_000);
+    for threads in ["1", "4"] {
+        let args = [
+            "--gfa",
+            gfa.as_str(),
+            "--gaf",
+            "-",
+            "-n",
+            "s",
+            "-t",
+            threads,
+        ];
+        assert_eq!(gafpack(&args, &compressed), plain);
+    }
+}
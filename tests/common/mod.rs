@@ -0,0 +1,113 @@
+//! Helpers shared by the integration tests
+#![allow(dead_code)]
+
+use flate2::{Compression, GzBuilder};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// Scratch directory for a test's input files, removed when dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create a directory unique to this process and `name`
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("gafpack-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    /// Write a file into the directory, returning its path
+    pub fn write(&self, file: &str, contents: impl AsRef<[u8]>) -> String {
+        let path = self.0.join(file);
+        std::fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Run gafpack with `stdin` piped in
+pub fn run(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_gafpack"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Run gafpack, which must succeed, returning its standard output
+pub fn gafpack(args: &[&str], stdin: &[u8]) -> String {
+    let output = run(args, stdin);
+    assert!(
+        output.status.success(),
+        "gafpack {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Number of nodes in the graph made by [`gfa`]
+pub const NODES: usize = 50;
+
+/// GFA with nodes 1 to [`NODES`], each as long as its ID
+pub fn gfa() -> String {
+    (1..=NODES)
+        .map(|id| format!("S\t{}\t{}\n", id, "A".repeat(id)))
+        .collect()
+}
+
+/// A GAF line aligning the whole of query `name` over nodes `node` and
+/// `node + 1` of [`gfa`]
+pub fn record(name: &str, node: usize) -> String {
+    let len = 2 * node + 1;
+    format!(
+        "{}\t100\t0\t100\t+\t>{}>{}\t{}\t0\t{}\t{}\t{}\t60\n",
+        name,
+        node,
+        node + 1,
+        len,
+        len,
+        len,
+        len
+    )
+}
+
+/// The empty block that terminates BGZF files, as given in the SAM
+/// specification
+pub const BGZF_EOF: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0, 0, 0, 0, 0, 0xff, 0x06, 0, b'B', b'C', 0x02, 0, 0x1b, 0, 0x03, 0, 0,
+    0, 0, 0, 0, 0, 0, 0,
+];
+
+/// Compress `data` as one BGZF block: a gzip member whose extra field holds
+/// a `BC` subfield with the block size minus 1
+pub fn bgzf_block(data: &[u8]) -> Vec<u8> {
+    const SUBFIELD: [u8; 4] = [b'B', b'C', 2, 0];
+    let mut encoder = GzBuilder::new()
+        .extra([&SUBFIELD[..], &[0, 0]].concat())
+        .write(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    let mut block = encoder.finish().unwrap();
+    let bsize = u16::try_from(block.len() - 1).unwrap();
+    let at = block.windows(4).position(|w| w == SUBFIELD).unwrap() + SUBFIELD.len();
+    block[at..at + 2].copy_from_slice(&bsize.to_le_bytes());
+    block
+}
+
+/// Compress `data` as BGZF blocks of up to `size` bytes and an EOF block
+pub fn bgzf(data: &[u8], size: usize) -> Vec<u8> {
+    data.chunks(size)
+        .chain([&[][..]])
+        .flat_map(bgzf_block)
+        .collect()
+}
@@ -0,0 +1,25 @@
+mod common;
+
+use common::{gafpack, gfa, record, TempDir};
+
+#[test]
+fn empty_gaf_inputs() {
+    let dir = TempDir::new("empty-input");
+    let gfa = dir.write("graph.gfa", gfa());
+    let gaf = dir.write("a.gaf", record("read1", 1));
+    let empty = dir.write("empty.gaf", "");
+    let args = ["--gfa", gfa.as_str(), "--gaf", gaf.as_str(), empty.as_str()];
+
+    // Samples without alignments get rows of zeros
+    let out = gafpack(&args, b"");
+    let rows = out.lines().skip(1).collect::<Vec<_>>();
+    assert_eq!(rows.len(), 2);
+    assert!(rows[0].starts_with("a\t1\t2\t0"));
+    assert!(rows[1].split('\t').skip(1).all(|v| v == "0"), "{}", rows[1]);
+
+    // An aligner writing nothing to standard input
+    for threads in ["1", "4"] {
+        let out = gafpack(&["--gfa", &gfa, "--gaf", "-", "-t", threads], b"");
+        assert_eq!(out.lines().count(), 2);
+    }
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use common::{gfa, record, TempDir, NODES};
+use gafpack::parallel::{process_lines, CHUNK_LINES};
+use gafpack::{pack_gaf, Error, GraphLengths, IdLayout, PackConfig, WeightMode};
+
+/// Several chunks of records grouped by query, with one query's records
+/// straddling the first chunk boundary
+fn records() -> String {
+    let mut gaf = String::new();
+    let mut lines = 0;
+    let mut q = 0;
+    while lines < 3 * CHUNK_LINES {
+        let hits = if lines + 50 > CHUNK_LINES && lines < CHUNK_LINES {
+            100
+        } else {
+            1 + q % 3
+        };
+        for hit in 0..hits {
+            gaf.push_str(&record(&format!("q{}", q), (q + hit * 7) % (NODES - 1) + 1));
+        }
+        lines += hits;
+        q += 1;
+    }
+    gaf
+}
+
+fn pack(gaf: &str, graph: &GraphLengths, config: &PackConfig, threads: usize) -> Vec<f64> {
+    let config = PackConfig {
+        threads,
+        ..config.clone()
+    };
+    pack_gaf(gaf, graph, &config).unwrap().0.values().to_vec()
+}
+
+fn assert_close(a: &[f64], b: &[f64]) {
+    assert_eq!(a.len(), b.len());
+    for (x, y) in a.iter().zip(b) {
+        assert!((x - y).abs() <= 1e-9 * x.abs().max(1.0), "{} != {}", x, y);
+    }
+}
+
+#[test]
+fn threads_agree_with_one_thread() {
+    let dir = TempDir::new("parallel-agree");
+    let gfa = dir.write("graph.gfa", gfa());
+    let gaf = dir.write("sample.gaf", records());
+    let graph = GraphLengths::from_gfa(&gfa, IdLayout::Compact, 1).unwrap();
+
+    let unweighted = PackConfig::default();
+    let weighted = |weight_mode| PackConfig {
+        weight_queries: true,
+        weight_mode,
+        ..Default::default()
+    };
+    let two_pass = weighted(WeightMode::TwoPass);
+    let grouped = weighted(WeightMode::Grouped);
+    let expected = pack(&gaf, &graph, &two_pass, 1);
+    for config in [&unweighted, &two_pass, &grouped] {
+        let single = pack(&gaf, &graph, config, 1);
+        for threads in [2, 4] {
+            assert_close(&pack(&gaf, &graph, config, threads), &single);
+        }
+    }
+    // Chunks are never cut inside a query's records, which would weight
+    // each part as a group of its own
+    assert_close(&pack(&gaf, &graph, &grouped, 4), &expected);
+}
+
+/// Count the lines of a file on several threads, failing at line `fail_at`
+fn count_lines(path: &str, keep_groups: bool, fail_at: usize) -> Result<usize, Error> {
+    let states = process_lines(
+        path,
+        4,
+        keep_groups,
+        || 0,
+        |count, line_no, _| {
+            if line_no == fail_at {
+                return Err(Error::io(path, std::io::Error::other("worker failed")));
+            }
+            *count += 1;
+            Ok(())
+        },
+        |_| Ok(()),
+    )?;
+    Ok(states.into_iter().sum())
+}
+
+#[test]
+fn worker_errors_are_returned() {
+    let dir = TempDir::new("parallel-worker");
+    let gaf = records();
+    let lines = gaf.lines().count();
+    let path = dir.write("sample.gaf", &gaf);
+    assert_eq!(count_lines(&path, false, 0).unwrap(), lines);
+    assert_eq!(count_lines(&path, true, 0).unwrap(), lines);
+
+    let error = count_lines(&path, false, 2 * CHUNK_LINES + 5).unwrap_err();
+    assert!(error.to_string().contains("worker failed"), "{}", error);
+
+    // A malformed record reports its own line, wherever its chunk starts
+    let mut malformed = gaf.lines().map(|l| format!("{}\n", l)).collect::<Vec<_>>();
+    malformed[2 * CHUNK_LINES + 5] = "q\t100\t0\t100\t+\t>1>2\n".to_string();
+    let path = dir.write("malformed.gaf", malformed.concat());
+    let graph =
+        GraphLengths::from_gfa(&dir.write("graph.gfa", gfa()), IdLayout::Compact, 1).unwrap();
+    let config = PackConfig {
+        threads: 4,
+        ..Default::default()
+    };
+    match pack_gaf(&path, &graph, &config) {
+        Err(Error::Record { line, .. }) => assert_eq!(line, 2 * CHUNK_LINES + 6),
+        other => panic!("expected a record error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn reader_errors_are_returned() {
+    let dir = TempDir::new("parallel-reader");
+    let gaf = records();
+
+    // Invalid UTF-8 past the first chunks
+    let mut bytes = gaf.clone().into_bytes();
+    let at = bytes.len() - 10;
+    bytes[at] = 0xff;
+    let path = dir.write("binary.gaf", bytes);
+    match count_lines(&path, false, 0) {
+        Err(Error::Io { source, .. }) => {
+            assert_eq!(source.kind(), std::io::ErrorKind::InvalidData)
+        }
+        other => panic!("expected an I/O error, got {:?}", other),
+    }
+
+    // A query recurring after its records ended
+    let path = dir.write("ungrouped.gaf", format!("{}{}", gaf, record("q0", 1)));
+    match count_lines(&path, true, 0) {
+        Err(Error::Ungrouped { line, query, .. }) => {
+            assert_eq!(line, gaf.lines().count() + 1);
+            assert_eq!(query, "q0");
+        }
+        other => panic!("expected an ungrouped error, got {:?}", other),
+    }
+    assert!(count_lines(&path, false, 0).is_ok());
+}
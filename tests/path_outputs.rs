@@ -0,0 +1,43 @@
+mod common;
+
+use common::{gafpack, gfa, record, run, TempDir};
+
+#[test]
+fn path_outputs_report_depth_in_bases() {
+    let dir = TempDir::new("path-outputs");
+    let gfa = dir.write("graph.gfa", gfa() + "P\tref\t1+,2+,3+\t*\n");
+    let gaf = dir.write(
+        "sample.gaf",
+        [record("read1", 1), record("read2", 1), record("read3", 2)].concat(),
+    );
+    let args = ["--gfa", gfa.as_str(), "--gaf", gaf.as_str(), "-n", "s"];
+
+    // Node 1 gets 2 x 1 base over 1 bp, node 2 3 x 2 bases over 2 bp and
+    // node 3 3 bases over 3 bp
+    let bedgraph = gafpack(
+        &[&args[..], &["--bedgraph", "-", "--ref-path", "ref"]].concat(),
+        b"",
+    );
+    assert_eq!(
+        bedgraph,
+        "track type=bedGraph name=\"s\"\nref\t0\t1\t2\nref\t1\t3\t3\nref\t3\t6\t1\n"
+    );
+
+    let scaled = [&args[..], &["-l", "--bedgraph", "-", "--ref-path", "ref"]].concat();
+    assert_eq!(gafpack(&scaled, b""), bedgraph);
+
+    for output in [
+        &["--bedgraph", "-", "--ref-path", "ref"][..],
+        &["--windows", "-", "--ref-path", "ref"][..],
+        &["--path-summary", "-"][..],
+    ] {
+        let with_mode = |mode| [&args[..], output, &["--count-mode", mode]].concat();
+        assert!(run(&with_mode("bases"), b"").status.success());
+        for mode in ["reads", "fraction"] {
+            let output = run(&with_mode(mode), b"");
+            assert!(!output.status.success());
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert!(stderr.contains("need --count-mode bases"), "{}", stderr);
+        }
+    }
+}
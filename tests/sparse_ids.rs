@@ -0,0 +1,43 @@
+mod common;
+
+use common::{gafpack, TempDir};
+
+/// Graph with node 4 missing from its ID range, and one alignment over it
+fn inputs(dir: &TempDir) -> (String, String) {
+    let gfa = dir.write("graph.gfa", "S\t1\tAAAA\nS\t2\tAAA\nS\t3\tAA\nS\t5\tA\n");
+    let gaf = dir.write(
+        "sample.gaf",
+        "read1\t10\t0\t10\t+\t>1>2>3>5\t10\t0\t10\t10\t10\t60\n",
+    );
+    (gfa, gaf)
+}
+
+#[test]
+fn sparse_ids_in_every_format() {
+    let dir = TempDir::new("sparse-ids");
+    let (gfa, gaf) = inputs(&dir);
+    let args = ["--gfa", gfa.as_str(), "--gaf", gaf.as_str(), "-n", "s"];
+
+    // Tabular: missing IDs are left out, columns keep their IDs
+    let tabular = gafpack(&args, b"");
+    assert_eq!(
+        tabular,
+        "#sample\tnode.1\tnode.2\tnode.3\tnode.5\ns\t4\t3\t2\t1\n"
+    );
+    let dense = gafpack(&[&args[..], &["--dense-ids"]].concat(), b"");
+    assert_eq!(
+        dense,
+        "#sample\tnode.1\tnode.2\tnode.3\tnode.4\tnode.5\ns\t4\t3\t2\t0\t1\n"
+    );
+
+    // Column: rows carry no IDs, so row i stays ID 1 + i
+    let column = gafpack(&[&args[..], &["-c"]].concat(), b"");
+    assert_eq!(column, "##sample: s\n#coverage\n4\n3\n2\n0\n1\n");
+
+    // Sparse: rows are labelled with their IDs
+    let sparse = gafpack(&[&args[..], &["-s"]].concat(), b"");
+    assert_eq!(
+        sparse,
+        "##sample: s\n#node\tcoverage\n1\t4\n2\t3\n3\t2\n5\t1\n"
+    );
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use common::{gfa, record, run, TempDir, NODES};
+use gafpack::pack::GROUPING_PROBE_RECORDS;
+use gafpack::{pack_gaf, Error, GraphLengths, IdLayout, PackConfig, WeightMode};
+
+/// Records of `queries` queries with one to three alignments each, grouped
+/// by query
+fn grouped_records(queries: usize) -> String {
+    let mut gaf = String::new();
+    for q in 0..queries {
+        for hit in 0..=q % 3 {
+            gaf.push_str(&record(&format!("q{}", q), (q + hit * 7) % (NODES - 1) + 1));
+        }
+    }
+    gaf
+}
+
+fn pack(gaf: &str, graph: &GraphLengths, mode: WeightMode) -> Result<Vec<f64>, Error> {
+    let config = PackConfig {
+        weight_queries: true,
+        weight_mode: mode,
+        ..Default::default()
+    };
+    pack_gaf(gaf, graph, &config).map(|(coverage, _)| coverage.values().to_vec())
+}
+
+fn assert_close(a: &[f64], b: &[f64]) {
+    assert_eq!(a.len(), b.len());
+    for (x, y) in a.iter().zip(b) {
+        assert!((x - y).abs() < 1e-9, "{} != {}", x, y);
+    }
+}
+
+#[test]
+fn grouped_and_two_pass_agree() {
+    let dir = TempDir::new("weighting-grouped");
+    let gfa = dir.write("graph.gfa", gfa());
+    let gaf = dir.write("grouped.gaf", grouped_records(1000));
+    let graph = GraphLengths::from_gfa(&gfa, IdLayout::Compact, 1).unwrap();
+
+    let two_pass = pack(&gaf, &graph, WeightMode::TwoPass).unwrap();
+    assert_close(&pack(&gaf, &graph, WeightMode::Grouped).unwrap(), &two_pass);
+    assert_close(&pack(&gaf, &graph, WeightMode::Auto).unwrap(), &two_pass);
+    // Weighting spreads each query's single unit over its alignments
+    let unweighted = pack_gaf(&gaf, &graph, &PackConfig::default()).unwrap().0;
+    let total = |values: &[f64]| values.iter().sum::<f64>();
+    assert!(total(&two_pass) < total(unweighted.values()));
+}
+
+#[test]
+fn auto_falls_back_to_two_passes_after_the_probe() {
+    let dir = TempDir::new("weighting-fallback");
+    let gfa = dir.write("graph.gfa", gfa());
+    let mut records = grouped_records(GROUPING_PROBE_RECORDS / 2 + 10);
+    let lines = records.lines().count();
+    assert!(lines > GROUPING_PROBE_RECORDS);
+    // The first query recurs once the probe has passed
+    records.push_str(&record("q0", 5));
+    let gaf = dir.write("late.gaf", records);
+    let graph = GraphLengths::from_gfa(&gfa, IdLayout::Compact, 1).unwrap();
+
+    let two_pass = pack(&gaf, &graph, WeightMode::TwoPass).unwrap();
+    assert_close(&pack(&gaf, &graph, WeightMode::Auto).unwrap(), &two_pass);
+    match pack(&gaf, &graph, WeightMode::Grouped) {
+        Err(Error::Ungrouped { line, query, .. }) => {
+            assert_eq!(line, lines + 1);
+            assert_eq!(query, "q0");
+        }
+        other => panic!("expected an ungrouped error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn ungrouped_stdin_fails() {
+    let dir = TempDir::new("weighting-stdin");
+    let gfa = dir.write("graph.gfa", gfa());
+    let gaf = [record("a", 1), record("b", 2), record("a", 3)].concat();
+
+    let output = run(&["--gfa", &gfa, "--gaf", "-", "-w"], gaf.as_bytes());
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("-:3: query `a` recurs"), "{}", stderr);
+
+    // The same records in a file are read twice instead
+    let file = dir.write("ungrouped.gaf", &gaf);
+    let output = run(&["--gfa", &gfa, "--gaf", &file, "-w"], b"");
+    assert!(output.status.success());
+}